@@ -0,0 +1,278 @@
+// Times the common operations of every container for several lengths and element sizes and
+// writes the results as CSV and Markdown, so that runs can be committed and diffed:
+//
+// cargo run --example bench --release -- [--quick] [--out DIR]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    env,
+    fmt::{Debug, Write as _},
+    fs,
+    hint::black_box,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use array_list::{
+    array::Array, growth::Fibonacci, matrix_array::MatrixArray, raw_array::RawArray,
+    single_array::SingleArray, sparse_array::SparseArray, vector_array::VectorArray, IArray,
+    IIterable, Wrapper,
+};
+
+// Counts the bytes in use, for the memory footprint of a filled container.
+struct CountingAlloc;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        IN_USE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        IN_USE.fetch_add(new_size, Ordering::Relaxed);
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// Inserts, removes and random gets are timed over this many operations at most, so the
+// quadratic containers still finish on long arrays.
+const OPS: usize = 1000;
+
+const METRICS: [(&str, &str); 9] = [
+    ("push", "ns/op"),
+    ("insert_front", "ns/op"),
+    ("insert_middle", "ns/op"),
+    ("remove_front", "ns/op"),
+    ("remove_middle", "ns/op"),
+    ("remove_back", "ns/op"),
+    ("get_random", "ns/op"),
+    ("iterate", "ns/elem"),
+    ("memory", "bytes/elem"),
+];
+
+trait Element: Copy + Default + PartialEq + Debug {
+    const NAME: &'static str;
+
+    fn new(i: usize) -> Self;
+}
+
+impl Element for u8 {
+    const NAME: &'static str = "u8";
+
+    fn new(i: usize) -> Self {
+        i as u8
+    }
+}
+
+impl Element for i64 {
+    const NAME: &'static str = "i64";
+
+    fn new(i: usize) -> Self {
+        i as i64
+    }
+}
+
+impl Element for [u64; 8] {
+    const NAME: &'static str = "[u64; 8]";
+
+    fn new(i: usize) -> Self {
+        [i as u64; 8]
+    }
+}
+
+struct Row {
+    container: &'static str,
+    element: &'static str,
+    len: usize,
+    values: Vec<f64>,
+}
+
+fn main() {
+    let mut quick = false;
+    let mut out = PathBuf::from("bench");
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quick" => quick = true,
+            "--out" => out = args.next().expect("--out needs a directory").into(),
+            _ => panic!("unknown argument {arg}, expected --quick or --out DIR"),
+        }
+    }
+    let lens: &[usize] = if quick {
+        &[1_000]
+    } else {
+        &[1_000, 10_000, 100_000]
+    };
+
+    let mut rows = Vec::new();
+    run_all::<u8>(lens, &mut rows);
+    run_all::<i64>(lens, &mut rows);
+    run_all::<[u64; 8]>(lens, &mut rows);
+
+    fs::create_dir_all(&out).unwrap();
+    fs::write(out.join("results.csv"), csv(&rows)).unwrap();
+    let markdown = markdown(&rows);
+    fs::write(out.join("results.md"), &markdown).unwrap();
+    print!("{markdown}");
+}
+
+fn run_all<E: Element>(lens: &[usize], rows: &mut Vec<Row>) {
+    // The single and sparse arrays push in linear time, so they stop at shorter lengths.
+    let short = 10_000;
+    for &len in lens {
+        run(rows, "Standard Vec", len, Wrapper::<E>::new);
+        if len <= short {
+            run(rows, "Single array", len, SingleArray::<E>::new);
+        }
+        run(rows, "Vector array (100)", len, || {
+            VectorArray::<E>::new(100)
+        });
+        run(rows, "Factor array (aka Array)", len, Array::<E>::new);
+        run(rows, "Fibonacci array", len, || {
+            RawArray::<E, _>::with_growth(Fibonacci)
+        });
+        run(rows, "Matrix array (100)", len, || {
+            MatrixArray::<E>::new(100)
+        });
+        run(
+            rows,
+            "Matrix array (adaptive)",
+            len,
+            MatrixArray::<E>::adaptive,
+        );
+        if len <= short {
+            run(rows, "Sparse array", len, SparseArray::<E>::new);
+        }
+    }
+}
+
+fn run<A, E>(rows: &mut Vec<Row>, container: &'static str, len: usize, create: impl Fn() -> A)
+where
+    A: IArray<E> + IIterable<E>,
+    E: Element,
+{
+    let filled = || {
+        let mut array = create();
+        (0..len).for_each(|i| array.push(E::new(i)));
+        array
+    };
+    let ops = OPS.min(len);
+
+    let before = IN_USE.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let array = filled();
+    let push = per_op(start, len);
+    let memory = (IN_USE.load(Ordering::Relaxed) - before) as f64 / len as f64;
+
+    let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+    let start = Instant::now();
+    for _ in 0..ops {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        black_box(array.get(rng as usize % len));
+    }
+    let get_random = per_op(start, ops);
+
+    let start = Instant::now();
+    black_box(
+        array
+            .iter()
+            .fold(0, |count, elem| count + (*elem != E::default()) as usize),
+    );
+    let iterate = per_op(start, len);
+    drop(array);
+
+    let values = vec![
+        push,
+        time_ops(&filled, ops, |array| array.insert(E::default(), 0)),
+        time_ops(&filled, ops, |array| {
+            array.insert(E::default(), array.size() / 2)
+        }),
+        time_ops(&filled, ops, |array| {
+            black_box(array.remove(0));
+        }),
+        time_ops(&filled, ops, |array| {
+            black_box(array.remove(array.size() / 2));
+        }),
+        time_ops(&filled, ops, |array| {
+            black_box(array.remove(array.size() - 1));
+        }),
+        get_random,
+        iterate,
+        memory,
+    ];
+    eprintln!("{container}, {}, n = {len}: done", E::NAME);
+    rows.push(Row {
+        container,
+        element: E::NAME,
+        len,
+        values,
+    });
+}
+
+// Times `ops` calls of `op` on a freshly filled array.
+fn time_ops<A>(filled: &impl Fn() -> A, ops: usize, mut op: impl FnMut(&mut A)) -> f64 {
+    let mut array = filled();
+    let start = Instant::now();
+    (0..ops).for_each(|_| op(&mut array));
+    per_op(start, ops)
+}
+
+fn per_op(start: Instant, ops: usize) -> f64 {
+    start.elapsed().as_nanos() as f64 / ops.max(1) as f64
+}
+
+fn csv(rows: &[Row]) -> String {
+    let mut out = String::from("container,element,len,metric,value,unit\n");
+    for row in rows {
+        for ((metric, unit), value) in METRICS.iter().zip(&row.values) {
+            let (container, element, len) = (row.container, row.element, row.len);
+            writeln!(
+                out,
+                "\"{container}\",\"{element}\",{len},{metric},{value:.1},{unit}"
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+// One table per element type and length, with a row per container.
+fn markdown(rows: &[Row]) -> String {
+    let mut out = String::from("# Benchmark results\n");
+    let mut groups: Vec<(&str, usize)> = rows.iter().map(|row| (row.element, row.len)).collect();
+    groups.dedup();
+    for (element, len) in groups {
+        writeln!(out, "\n## `{element}`, n = {len}\n").unwrap();
+        out.push_str("| container |");
+        METRICS
+            .iter()
+            .for_each(|(metric, unit)| write!(out, " {metric} ({unit}) |").unwrap());
+        out.push_str("\n|---|");
+        out.push_str(&"---:|".repeat(METRICS.len()));
+        out.push('\n');
+        for row in rows
+            .iter()
+            .filter(|row| (row.element, row.len) == (element, len))
+        {
+            write!(out, "| {} |", row.container).unwrap();
+            row.values
+                .iter()
+                .for_each(|value| write!(out, " {value:.1} |").unwrap());
+            out.push('\n');
+        }
+    }
+    out
+}
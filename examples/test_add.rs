@@ -1,8 +1,14 @@
 use std::time::{Duration, Instant};
 
 use array_list::{
-    array::Array, matrix_array::MatrixArray, single_array::SingleArray, sparse_array::SparseArray,
-    vector_array::VectorArray, IArray, Wrapper,
+    array::Array,
+    growth::{Factor, Fibonacci},
+    matrix_array::MatrixArray,
+    raw_array::RawArray,
+    single_array::SingleArray,
+    sparse_array::SparseArray,
+    vector_array::VectorArray,
+    IArray, Wrapper,
 };
 
 fn main() {
@@ -11,6 +17,10 @@ fn main() {
     run_test("Vector array (10)", || VectorArray::new(10));
     run_test("Vector array (100)", || VectorArray::new(10));
     run_test("Factor array (aka Array)", Array::new);
+    run_test("Factor array (1.5)", || {
+        RawArray::with_growth(Factor::new(1.5))
+    });
+    run_test("Fibonacci array", || RawArray::with_growth(Fibonacci));
     run_test("Matrix array(10)", || MatrixArray::new(10));
     run_test("Matrix array (100)", || MatrixArray::new(100));
     run_test("Sparse array", SparseArray::new);
@@ -37,4 +47,4 @@ fn test_add_integers(array: &mut dyn IArray<i64>, n: usize) -> Duration {
         array.push(i as i64);
     }
     Instant::now().duration_since(start)
-}
\ No newline at end of file
+}
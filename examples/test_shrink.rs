@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use array_list::{
+    array::Array,
+    growth::{Fibonacci, GrowthPolicy},
+    raw_array::RawArray,
+    single_array::SingleArray,
+    vector_array::VectorArray,
+    IArray,
+};
+
+fn main() {
+    for auto_shrink in [false, true] {
+        println!("auto shrink: {auto_shrink}");
+        run_test("Factor array (aka Array)", auto_shrink, 7, Array::new);
+        run_test("Vector array (100)", auto_shrink, 6, || {
+            VectorArray::new(100)
+        });
+        run_test("Fibonacci array", auto_shrink, 7, || {
+            RawArray::with_growth(Fibonacci)
+        });
+        run_test("Single array", auto_shrink, 5, SingleArray::new);
+    }
+}
+
+fn run_test<F, G>(title: &str, auto_shrink: bool, max_power: u32, create: F)
+where
+    F: Fn() -> RawArray<i64, G>,
+    G: GrowthPolicy,
+{
+    for i in 1..=max_power {
+        let mut a = create();
+        a.set_auto_shrink(auto_shrink);
+        let n = 10_usize.pow(i);
+        let (burst, jitter) = test_burst(&mut a, n);
+        println!(
+            "{title}: n = {n}, push+pop burst in {burst:?}, {n} push/pop pairs at the shrink edge in {jitter:?}, final capacity {}",
+            a.capacity()
+        );
+    }
+}
+
+// Fills the array, drains it to a quarter and then pushes and pops around that length.
+fn test_burst<G: GrowthPolicy>(array: &mut RawArray<i64, G>, n: usize) -> (Duration, Duration) {
+    let start = Instant::now();
+    for i in 0..n {
+        array.push(i as i64);
+    }
+    while array.len() > n / 4 {
+        array.pop();
+    }
+    let burst = Instant::now().duration_since(start);
+    let start = Instant::now();
+    for i in 0..n {
+        array.push(i as i64);
+        array.pop();
+    }
+    (burst, Instant::now().duration_since(start))
+}
@@ -0,0 +1,37 @@
+use array_list::{
+    array::Array, matrix_array::MatrixArray, single_array::SingleArray, sparse_array::SparseArray,
+    stats, vector_array::VectorArray, IArray, Wrapper,
+};
+
+fn main() {
+    run_test("Standard Vec", Wrapper::new);
+    run_test("Single array", SingleArray::new);
+    run_test("Vector array (10)", || VectorArray::new(10));
+    run_test("Vector array (100)", || VectorArray::new(100));
+    run_test("Factor array (aka Array)", Array::new);
+    run_test("Matrix array (10)", || MatrixArray::new(10));
+    run_test("Matrix array (100)", || MatrixArray::new(100));
+    run_test("Sparse array", SparseArray::new);
+}
+
+fn run_test<F, Array>(title: &str, create: F)
+where
+    F: Fn() -> Array,
+    Array: IArray<i64>,
+{
+    for i in 1..6 {
+        let n = 10_usize.pow(i);
+        let (_, push) = stats::measure(|| {
+            let mut a = create();
+            (0..n).for_each(|i| a.push(i as i64));
+            a
+        });
+        let mut a = create();
+        (0..n).for_each(|i| a.push(i as i64));
+        let (_, insert) = stats::measure(|| a.insert(-1, 0));
+        println!(
+            "{title}: n = {n}, push: {} allocations, {} reallocations, peak capacity {}; insert at front: {} bytes moved",
+            push.allocations, push.reallocations, push.peak_capacity, insert.bytes_moved
+        );
+    }
+}
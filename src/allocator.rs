@@ -0,0 +1,353 @@
+use std::{
+    alloc::{self, Layout},
+    cell::RefCell,
+    cmp,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A stable stand-in for the unstable `std::alloc::Allocator`.
+///
+/// # Safety
+/// `allocate` and `reallocate` must return blocks valid for the requested layout that stay
+/// valid until they are passed to `deallocate` or `reallocate`. Layouts are never zero-sized.
+pub unsafe trait RawAllocator {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// # Safety
+    /// `ptr` must come from this allocator with the same `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// # Safety
+    /// `ptr` must come from this allocator with `old_layout`; on success it is invalidated.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        let new_layout = Layout::from_size_align(new_size, old_layout.align()).ok()?;
+        let new_ptr = self.allocate(new_layout)?;
+        let count = cmp::min(old_layout.size(), new_size);
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), count);
+        self.deallocate(ptr, old_layout);
+        Some(new_ptr)
+    }
+}
+
+// Lets several arrays share one arena or counter.
+unsafe impl<A: RawAllocator + ?Sized> RawAllocator for &A {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        (**self).reallocate(ptr, old_layout, new_size)
+    }
+}
+
+// The global allocator, what `std::alloc::alloc` and friends use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Global;
+
+unsafe impl RawAllocator for Global {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        NonNull::new(unsafe { alloc::alloc(layout) })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        alloc::dealloc(ptr.as_ptr(), layout)
+    }
+
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        NonNull::new(alloc::realloc(ptr.as_ptr(), old_layout, new_size))
+    }
+}
+
+// A bump allocator: memory is handed out from chunks and only returned when the arena
+// is dropped or reset. The most recent block can still grow or shrink in place.
+#[derive(Debug)]
+pub struct Arena {
+    state: RefCell<ArenaState>,
+}
+
+#[derive(Debug)]
+struct ArenaState {
+    chunks: Vec<(NonNull<u8>, Layout)>,
+    chunk_size: usize,
+    offset: usize,
+    last: Option<usize>,
+    allocated: usize,
+}
+
+// The chunks are owned by the arena; the `RefCell` still keeps it from being shared.
+unsafe impl Send for Arena {}
+
+impl Arena {
+    const CHUNK_ALIGN: usize = 16;
+
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            state: RefCell::new(ArenaState {
+                chunks: Vec::new(),
+                chunk_size: cmp::max(chunk_size, 1),
+                offset: 0,
+                last: None,
+                allocated: 0,
+            }),
+        }
+    }
+
+    // Bytes handed out so far, including blocks that were given back.
+    pub fn allocated_bytes(&self) -> usize {
+        self.state.borrow().allocated
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.state.borrow().chunks.len()
+    }
+
+    // Frees every chunk but the newest, which is reused from its start.
+    pub fn reset(&mut self) {
+        let state = self.state.get_mut();
+        let keep = state.chunks.pop();
+        for (ptr, layout) in state.chunks.drain(..) {
+            unsafe { alloc::dealloc(ptr.as_ptr(), layout) };
+        }
+        state.chunks.extend(keep);
+        state.offset = 0;
+        state.last = None;
+        state.allocated = 0;
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new(4096)
+    }
+}
+
+impl ArenaState {
+    fn current(&self) -> Option<(NonNull<u8>, Layout)> {
+        self.chunks.last().copied()
+    }
+
+    fn bump(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let fits = |state: &Self| {
+            let (chunk, chunk_layout) = state.current()?;
+            let base = chunk.as_ptr() as usize;
+            let start = (base + state.offset).checked_next_multiple_of(layout.align())? - base;
+            (start.checked_add(layout.size())? <= chunk_layout.size()).then_some(start)
+        };
+        let start = match fits(self) {
+            Some(start) => start,
+            None => {
+                let size = cmp::max(self.chunk_size, layout.size() + layout.align());
+                let chunk_layout = Layout::from_size_align(size, Arena::CHUNK_ALIGN).ok()?;
+                let chunk = NonNull::new(unsafe { alloc::alloc(chunk_layout) })?;
+                self.chunks.push((chunk, chunk_layout));
+                self.offset = 0;
+                fits(self)?
+            }
+        };
+        self.offset = start + layout.size();
+        self.last = Some(start);
+        self.allocated += layout.size();
+        let (chunk, _) = self.current()?;
+        NonNull::new(unsafe { chunk.as_ptr().add(start) })
+    }
+
+    fn is_last(&self, ptr: NonNull<u8>) -> bool {
+        match (self.current(), self.last) {
+            (Some((chunk, _)), Some(start)) => unsafe { chunk.as_ptr().add(start) == ptr.as_ptr() },
+            _ => false,
+        }
+    }
+}
+
+unsafe impl RawAllocator for Arena {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.state.borrow_mut().bump(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        let mut state = self.state.borrow_mut();
+        // Only the newest block can be given back; the rest waits for the arena.
+        if let (true, Some(start)) = (state.is_last(ptr), state.last) {
+            state.offset = start;
+            state.last = None;
+        }
+    }
+
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.is_last(ptr) {
+                let (_, chunk_layout) = state.current()?;
+                let start = state.last?;
+                if start + new_size <= chunk_layout.size() {
+                    state.offset = start + new_size;
+                    state.allocated += new_size.saturating_sub(old_layout.size());
+                    return Some(ptr);
+                }
+            }
+        }
+        let new_layout = Layout::from_size_align(new_size, old_layout.align()).ok()?;
+        let new_ptr = self.allocate(new_layout)?;
+        let count = cmp::min(old_layout.size(), new_size);
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), count);
+        Some(new_ptr)
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for (ptr, layout) in self.state.get_mut().chunks.drain(..) {
+            unsafe { alloc::dealloc(ptr.as_ptr(), layout) };
+        }
+    }
+}
+
+// Wraps another allocator and counts what goes through it.
+#[derive(Debug, Default)]
+pub struct Counting<A = Global> {
+    inner: A,
+    allocations: AtomicUsize,
+    reallocations: AtomicUsize,
+    deallocations: AtomicUsize,
+    in_use: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl<A: RawAllocator> Counting<A> {
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            allocations: AtomicUsize::new(0),
+            reallocations: AtomicUsize::new(0),
+            deallocations: AtomicUsize::new(0),
+            in_use: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn allocations(&self) -> usize {
+        self.allocations.load(Ordering::Relaxed)
+    }
+
+    pub fn reallocations(&self) -> usize {
+        self.reallocations.load(Ordering::Relaxed)
+    }
+
+    pub fn deallocations(&self) -> usize {
+        self.deallocations.load(Ordering::Relaxed)
+    }
+
+    // Bytes currently allocated and the most that were ever allocated at once.
+    pub fn bytes_in_use(&self) -> usize {
+        self.in_use.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_bytes(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    fn grew(&self, bytes: usize) {
+        let in_use = self.in_use.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(in_use, Ordering::Relaxed);
+    }
+}
+
+unsafe impl<A: RawAllocator> RawAllocator for Counting<A> {
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let ptr = self.inner.allocate(layout)?;
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.grew(layout.size());
+        Some(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner.deallocate(ptr, layout);
+        self.deallocations.fetch_add(1, Ordering::Relaxed);
+        self.in_use.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_size: usize,
+    ) -> Option<NonNull<u8>> {
+        let new_ptr = self.inner.reallocate(ptr, old_layout, new_size)?;
+        self.reallocations.fetch_add(1, Ordering::Relaxed);
+        if new_size >= old_layout.size() {
+            self.grew(new_size - old_layout.size());
+        } else {
+            self.in_use
+                .fetch_sub(old_layout.size() - new_size, Ordering::Relaxed);
+        }
+        Some(new_ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_bumps_and_grows_in_place() {
+        let arena = Arena::new(64);
+        let a = arena.allocate(Layout::new::<u64>()).unwrap();
+        let b = arena.allocate(Layout::new::<u8>()).unwrap();
+        let c = arena.allocate(Layout::new::<u32>()).unwrap();
+        assert_eq!(0, c.as_ptr() as usize % 4);
+        assert_eq!(1, arena.chunk_count());
+        unsafe {
+            assert_eq!(Some(c), arena.reallocate(c, Layout::new::<u32>(), 16));
+            // `a` is not the newest block, so it has to move.
+            let moved = arena.reallocate(a, Layout::new::<u64>(), 16).unwrap();
+            assert_ne!(a, moved);
+            arena.deallocate(b, Layout::new::<u8>());
+        }
+        arena
+            .allocate(Layout::from_size_align(100, 8).unwrap())
+            .unwrap();
+        assert_eq!(2, arena.chunk_count());
+    }
+
+    #[test]
+    fn counting() {
+        let counting = Counting::new(Global);
+        let layout = Layout::array::<u32>(4).unwrap();
+        unsafe {
+            let ptr = counting.allocate(layout).unwrap();
+            let ptr = counting.reallocate(ptr, layout, 64).unwrap();
+            assert_eq!(64, counting.bytes_in_use());
+            counting.deallocate(ptr, Layout::from_size_align(64, 4).unwrap());
+        }
+        assert_eq!(1, counting.allocations());
+        assert_eq!(1, counting.reallocations());
+        assert_eq!(1, counting.deallocations());
+        assert_eq!(0, counting.bytes_in_use());
+        assert_eq!(64, counting.peak_bytes());
+    }
+}
@@ -1,188 +1,82 @@
-use std::{
-    alloc::{self, Layout},
-    marker::PhantomData,
-    mem,
-    ops::{Deref, DerefMut},
-    ptr::{self, NonNull},
+use crate::{
+    allocator::{Global, RawAllocator},
+    growth::Factor,
+    raw_array::RawArray,
 };
 
-use crate::IArray;
+// Grows by doubling: amortized O(1) push at the cost of up to half the buffer unused.
+pub type Array<T, A = Global> = RawArray<T, Factor, A>;
 
-#[derive(Debug)]
-pub struct Array<T> {
-    ptr: NonNull<T>,
-    cap: usize,
-    len: usize,
-    _marker: PhantomData<T>,
-}
-
-impl<T> Default for Array<T> {
-    fn default() -> Self {
-        assert!(mem::size_of::<T>() != 0, "We're not ready to handle ZSTs");
-        Array {
-            ptr: NonNull::dangling(),
-            len: 0,
-            cap: 0,
-            _marker: PhantomData,
-        }
-    }
-}
-
-impl<T> Drop for Array<T> {
-    fn drop(&mut self) {
-        if self.cap != 0 {
-            while self.pop().is_some() {}
-            let layout = Layout::array::<T>(self.cap).unwrap();
-            unsafe {
-                alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
-            }
-        }
-    }
-}
-
-impl<T> Deref for Array<T> {
-    type Target = [T];
-    fn deref(&self) -> &[T] {
-        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
-    }
-}
-
-impl<T> DerefMut for Array<T> {
-    fn deref_mut(&mut self) -> &mut [T] {
-        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
-    }
-}
-
-impl<T> Array<T> {
+impl<T> RawArray<T, Factor> {
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn pop(&mut self) -> Option<T> {
-        if self.len == 0 {
-            None
-        } else {
-            self.len -= 1;
-            unsafe { Some(ptr::read(self.ptr.as_ptr().add(self.len))) }
-        }
-    }
-
-    fn grow(&mut self) {
-        let (new_cap, new_layout) = if self.cap == 0 {
-            (1, Layout::array::<T>(1).unwrap())
-        } else {
-            // This can't overflow since self.cap <= isize::MAX.
-            let new_cap = 2 * self.cap;
-            // `Layout::array` checks that the number of bytes is <= usize::MAX,
-            // but this is redundant since old_layout.size() <= isize::MAX,
-            // so the `unwrap` should never fail.
-            let new_layout = Layout::array::<T>(new_cap).unwrap();
-            (new_cap, new_layout)
-        };
-        // Ensure that the new allocation doesn't exceed `isize::MAX` bytes.
-        assert!(
-            new_layout.size() <= isize::MAX as usize,
-            "Allocation too large"
-        );
-        let new_ptr = if self.cap == 0 {
-            unsafe { alloc::alloc(new_layout) }
-        } else {
-            let old_layout = Layout::array::<T>(self.cap).unwrap();
-            let old_ptr = self.ptr.as_ptr() as *mut u8;
-            unsafe { alloc::realloc(old_ptr, old_layout, new_layout.size()) }
-        };
-        // If allocation fails, `new_ptr` will be null, in which case we abort.
-        self.ptr = match NonNull::new(new_ptr as *mut T) {
-            Some(p) => p,
-            None => alloc::handle_alloc_error(new_layout),
-        };
-        self.cap = new_cap;
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_growth(capacity, Factor::default())
     }
 }
 
-impl<T> IArray<T> for Array<T> {
-    fn size(&self) -> usize {
-        self.len
-    }
-
-    fn push(&mut self, elem: T) {
-        self.insert(elem, self.len);
+impl<T, A: RawAllocator> RawArray<T, Factor, A> {
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_growth_in(Factor::default(), alloc)
     }
 
-    fn get(&self, index: usize) -> &T {
-        (self as &[T]).get(index).unwrap()
-    }
-
-    fn insert(&mut self, elem: T, index: usize) {
-        assert!(index <= self.len, "index out of bounds");
-        if self.cap == self.len {
-            self.grow();
-        }
-        unsafe {
-            ptr::copy(
-                self.ptr.as_ptr().add(index),
-                self.ptr.as_ptr().add(index + 1),
-                self.len - index,
-            );
-            ptr::write(self.ptr.as_ptr().add(index), elem);
-            self.len += 1;
-        }
-    }
-
-    fn remove(&mut self, index: usize) -> T {
-        // Note: `<` because it's *not* valid to remove after everything
-        assert!(index < self.len, "index out of bounds");
-        unsafe {
-            self.len -= 1;
-            let result = ptr::read(self.ptr.as_ptr().add(index));
-            ptr::copy(
-                self.ptr.as_ptr().add(index + 1),
-                self.ptr.as_ptr().add(index),
-                self.len - index,
-            );
-            result
-        }
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self::with_capacity_and_growth_in(capacity, Factor::default(), alloc)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{single_array::SingleArray, IArray};
 
-    #[test]
-    fn it_works() {
-        let mut bytes = Array::<u8>::new();
-        bytes.push(42);
-        assert_eq!(42, bytes.pop().unwrap());
-        assert!(bytes.pop().is_none());
-    }
+    crate::conformance_tests!(conformance, Array::new, |i| i as i64);
+    // The same suite with zero-sized elements, which never touch the allocator.
+    crate::conformance_tests!(conformance_zst, Array::new, |_| ());
 
     #[test]
-    fn bells_and_whistles() {
-        let mut bytes = Array::<u8>::new();
-        (0..=u8::MAX).for_each(|x| bytes.push(x));
-        assert_eq!(256, bytes.len());
-        assert_eq!(22, bytes[22]);
-        bytes[255] = 42;
-        assert_eq!(&42, bytes.last().unwrap());
-        *bytes.first_mut().unwrap() = 255;
-        assert_eq!(*bytes.first().unwrap(), bytes.get(254) + 1);
+    fn custom_allocators() {
+        use crate::allocator::{Arena, Counting};
+
+        let counting = Counting::new(Global);
+        let mut array = Array::new_in(&counting);
+        (0..1024_u32).for_each(|x| array.push(x));
+        assert_eq!(1, counting.allocations());
+        assert_eq!(10, counting.reallocations());
+        assert_eq!(4096, counting.bytes_in_use());
+        let sum: u32 = array.into_iter().sum();
+        assert_eq!(523776, sum);
+        assert_eq!(1, counting.deallocations());
+        assert_eq!(0, counting.bytes_in_use());
+
+        let arena = Arena::new(1024);
+        let mut strings = Array::with_capacity_in(4, &arena);
+        let mut numbers = Array::new_in(&arena);
+        for i in 0..100_u64 {
+            strings.push(i.to_string());
+            numbers.push(i);
+        }
+        assert_eq!("99", strings[99]);
+        assert_eq!(4950, numbers.iter().sum::<u64>());
+        assert!(arena.allocated_bytes() >= 100 * 8 + 100 * 24);
     }
 
     #[test]
-    fn array_interface() {
-        let mut array = Array::<i64>::new();
-        array.insert(42, 0);
-        array.insert(1024, 0);
-        array.insert(-339, 2);
-        array.insert(-851, 1);
-        assert_eq!(&1024, array.get(0));
-        assert_eq!(&(-851), array.get(1));
-        assert_eq!(&42, array.get(2));
-        assert_eq!(&(-339), array.get(3));
-        assert_eq!(1024, array.remove(0));
-        assert_eq!(&(-851), array.get(0));
-        assert_eq!(&42, array.get(1));
-        assert_eq!(&(-339), array.get(2));
+    fn clone_and_compare() {
+        use std::collections::HashSet;
+
+        let array: Array<String> = ["a", "b"].map(String::from).into_iter().collect();
+        let copy = array.clone();
+        assert_eq!(array, copy);
+        let single: SingleArray<String> = copy.clone().into_iter().collect();
+        assert_eq!(array, single);
+        assert_eq!(r#"["a", "b"]"#, format!("{:?}", single));
+        let mut longer = copy.clone();
+        longer.push(String::from("c"));
+        assert!(array < longer);
+        let set: HashSet<Array<String>> = [array, copy, longer].into_iter().collect();
+        assert_eq!(2, set.len());
     }
 }
@@ -0,0 +1,276 @@
+// A behavioral suite for `IArray` implementations, with `Vec` as the oracle.
+//
+// Every check takes a factory for empty arrays and `elem`, which turns an index into an
+// element, so any element type can be used; an `elem` that repeats values exercises
+// `dedup` and, for `SparseArray`, default elements. Checks panic on the first difference.
+// `conformance_tests!` turns the whole suite into one `#[test]` per check.
+use std::fmt::Debug;
+
+use crate::{error::IndexError, IArray, IIterable};
+
+// Long enough to cross many block boundaries of a small `MatrixArray`.
+pub const LEN: usize = 100;
+
+pub fn run_all<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    push_and_get(&factory, &elem);
+    insert(&factory, &elem);
+    remove(&factory, &elem);
+    set_and_get_mut(&factory, &elem);
+    checked_access(&factory, &elem);
+    fallible(&factory, &elem);
+    bulk_removal(&factory, &elem);
+    bulk_insertion(&factory, &elem);
+}
+
+// Fails with the position of the first difference.
+pub fn assert_matches<A, T>(array: &A, oracle: &[T])
+where
+    A: IArray<T> + IIterable<T>,
+    T: PartialEq + Debug,
+{
+    assert_eq!(oracle.len(), array.size(), "size");
+    for (index, expected) in oracle.iter().enumerate() {
+        assert_eq!(expected, array.get(index), "get({index})");
+        assert_eq!(Some(expected), array.try_get(index), "try_get({index})");
+    }
+    assert!(array.iter().eq(oracle.iter()), "iter");
+}
+
+fn filled<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T, len: usize) -> (A, Vec<T>)
+where
+    A: IArray<T>,
+    T: Clone,
+{
+    let mut array = factory();
+    let mut oracle = Vec::new();
+    for index in 0..len {
+        array.push(elem(index));
+        oracle.push(elem(index));
+    }
+    (array, oracle)
+}
+
+pub fn push_and_get<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let (array, oracle) = filled(&factory, &elem, 0);
+    assert_matches(&array, &oracle);
+    let (array, oracle) = filled(&factory, &elem, LEN);
+    assert_matches(&array, &oracle);
+}
+
+pub fn insert<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let mut array = factory();
+    let mut oracle = Vec::new();
+    for index in 0..LEN {
+        // Front, back and everything in between.
+        let position = match index % 4 {
+            0 => 0,
+            1 => oracle.len(),
+            _ => index * 7 % (oracle.len() + 1),
+        };
+        array.insert(elem(index), position);
+        oracle.insert(position, elem(index));
+        assert_eq!(oracle.len(), array.size());
+    }
+    assert_matches(&array, &oracle);
+}
+
+pub fn remove<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let (mut array, mut oracle) = filled(&factory, &elem, LEN);
+    let mut step = 0;
+    while !oracle.is_empty() {
+        let position = match step % 3 {
+            0 => 0,
+            1 => oracle.len() - 1,
+            _ => step * 5 % oracle.len(),
+        };
+        assert_eq!(
+            oracle.remove(position),
+            array.remove(position),
+            "remove({position})"
+        );
+        step += 1;
+    }
+    assert_matches(&array, &oracle);
+    array.push(elem(0));
+    oracle.push(elem(0));
+    assert_matches(&array, &oracle);
+}
+
+pub fn set_and_get_mut<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let (mut array, mut oracle) = filled(&factory, &elem, LEN);
+    for index in (0..LEN).step_by(3) {
+        let replacement = elem(LEN - index);
+        assert_eq!(oracle[index], array.set(index, replacement.clone()));
+        oracle[index] = replacement;
+    }
+    for index in (1..LEN).step_by(4) {
+        *array.get_mut(index).unwrap() = elem(index + 1);
+        oracle[index] = elem(index + 1);
+    }
+    assert_matches(&array, &oracle);
+}
+
+pub fn checked_access<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let (mut array, mut oracle) = filled(&factory, &elem, LEN);
+    assert_eq!(None, array.try_get(LEN));
+    assert!(array.get_mut(LEN).is_none());
+    assert_eq!(Err(IndexError::new(LEN, LEN)), array.try_remove(LEN));
+    assert_eq!(Ok(oracle.remove(0)), array.try_remove(0));
+    assert_matches(&array, &oracle);
+
+    let mut empty = factory();
+    assert_eq!(None, empty.try_get(0));
+    assert!(empty.get_mut(0).is_none());
+    assert_eq!(Err(IndexError::new(0, 0)), empty.try_remove(0));
+}
+
+pub fn fallible<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    // An empty array of zero-sized elements can hold `usize::MAX` of them; one more cannot.
+    let mut array = factory();
+    array.push(elem(0));
+    assert!(array.try_reserve(usize::MAX).is_err());
+    let mut array = factory();
+    assert!(array.try_reserve(LEN).is_ok());
+    let mut oracle = Vec::new();
+    for index in 0..LEN {
+        if index % 2 == 0 {
+            array.try_push(elem(index)).unwrap();
+            oracle.push(elem(index));
+        } else {
+            array.try_insert(elem(index), index / 2).unwrap();
+            oracle.insert(index / 2, elem(index));
+        }
+    }
+    assert_matches(&array, &oracle);
+}
+
+pub fn bulk_removal<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let (mut array, mut oracle) = filled(&factory, &elem, LEN);
+    let mut seen = 0;
+    array.retain(|_| {
+        seen += 1;
+        seen % 3 != 0
+    });
+    let mut seen = 0;
+    oracle.retain(|_| {
+        seen += 1;
+        seen % 3 != 0
+    });
+    assert_matches(&array, &oracle);
+
+    let drained: Vec<T> = array.drain(5..20).collect();
+    assert_eq!(oracle.drain(5..20).collect::<Vec<_>>(), drained);
+    assert_matches(&array, &oracle);
+
+    array.dedup_by_key(|x| x.clone());
+    oracle.dedup_by_key(|x| x.clone());
+    assert_matches(&array, &oracle);
+
+    array.truncate(oracle.len() + 1);
+    array.truncate(oracle.len() / 2);
+    oracle.truncate(oracle.len() / 2);
+    assert_matches(&array, &oracle);
+
+    array.clear();
+    assert_matches(&array, &[]);
+    array.push(elem(0));
+    assert_matches(&array, &[elem(0)]);
+}
+
+pub fn bulk_insertion<A, T>(factory: impl Fn() -> A, elem: impl Fn(usize) -> T)
+where
+    A: IArray<T> + IIterable<T>,
+    T: Clone + PartialEq + Debug,
+{
+    let (mut array, mut oracle) = filled(&factory, &elem, LEN / 2);
+    for (index, count) in [(0, 3), (LEN / 4, 10), (LEN / 2 + 13, 0), (LEN / 2 + 13, 7)] {
+        let items = (0..count).map(|i| elem(LEN + i));
+        array.insert_many(index, items.clone());
+        oracle.splice(index..index, items);
+        assert_matches(&array, &oracle);
+    }
+}
+
+// Generates one `#[test]` per check, in a module named `$name`:
+// `conformance_tests!(matrix, || MatrixArray::new(3), |i| i as i64);`
+#[macro_export]
+macro_rules! conformance_tests {
+    ($name:ident, $factory:expr, $elem:expr) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #[test]
+            fn push_and_get() {
+                $crate::conformance::push_and_get($factory, $elem);
+            }
+
+            #[test]
+            fn insert() {
+                $crate::conformance::insert($factory, $elem);
+            }
+
+            #[test]
+            fn remove() {
+                $crate::conformance::remove($factory, $elem);
+            }
+
+            #[test]
+            fn set_and_get_mut() {
+                $crate::conformance::set_and_get_mut($factory, $elem);
+            }
+
+            #[test]
+            fn checked_access() {
+                $crate::conformance::checked_access($factory, $elem);
+            }
+
+            #[test]
+            fn fallible() {
+                $crate::conformance::fallible($factory, $elem);
+            }
+
+            #[test]
+            fn bulk_removal() {
+                $crate::conformance::bulk_removal($factory, $elem);
+            }
+
+            #[test]
+            fn bulk_insertion() {
+                $crate::conformance::bulk_insertion($factory, $elem);
+            }
+        }
+    };
+}
@@ -0,0 +1,307 @@
+// Differential testing, internal to the crate: operation sequences run on a container and
+// on `Vec` in lockstep. The tests generate random ones and shrink failures to a minimal
+// reproduction; the fuzz harnesses decode their input into the same operations.
+use crate::{IArray, IIterable, Wrapper};
+
+#[cfg(test)]
+use {
+    crate::conformance::LEN,
+    std::panic::{self, AssertUnwindSafe},
+};
+
+// One step of a random operation sequence. Indices are reduced modulo the current length
+// when the step runs, so every sequence is valid and stays valid while it is shrunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Push(u8),
+    Insert(usize, u8),
+    Remove(usize),
+    Get(usize),
+    Set(usize, u8),
+    Truncate(usize),
+    Drain(usize, usize),
+    Retain(u8),
+    InsertMany(usize, u8),
+}
+
+// Small values, so that `SparseArray` sees plenty of zeros and `dedup` has runs to find.
+fn value(byte: u8) -> i64 {
+    i64::from(byte % 4)
+}
+
+// Replays `ops` on `array` and on `Wrapper`; the error names the first step that differs.
+pub(crate) fn check_ops<A>(array: &mut A, ops: &[Op]) -> Result<(), String>
+where
+    A: IArray<i64> + IIterable<i64>,
+{
+    let mut oracle = Wrapper::new();
+    for (step, &op) in ops.iter().enumerate() {
+        let len = oracle.size();
+        let differs = |what: String| Err(format!("step {step}, {op:?}: {what}"));
+        match op {
+            Op::Push(byte) => {
+                array.push(value(byte));
+                oracle.push(value(byte));
+            }
+            Op::Insert(index, byte) => {
+                let index = index % (len + 1);
+                array.insert(value(byte), index);
+                oracle.insert(value(byte), index);
+            }
+            Op::Remove(index) if len > 0 => {
+                let (got, expected) = (array.remove(index % len), oracle.remove(index % len));
+                if got != expected {
+                    return differs(format!("removed {got}, expected {expected}"));
+                }
+            }
+            Op::Get(index) => {
+                let (got, expected) = (
+                    array.try_get(index % (len + 1)),
+                    oracle.try_get(index % (len + 1)),
+                );
+                if got != expected {
+                    return differs(format!("got {got:?}, expected {expected:?}"));
+                }
+            }
+            Op::Set(index, byte) if len > 0 => {
+                let (got, expected) = (
+                    array.set(index % len, value(byte)),
+                    oracle.set(index % len, value(byte)),
+                );
+                if got != expected {
+                    return differs(format!("replaced {got}, expected {expected}"));
+                }
+            }
+            Op::Truncate(new_len) => {
+                array.truncate(new_len % (len + 1));
+                oracle.truncate(new_len % (len + 1));
+            }
+            Op::Drain(start, count) => {
+                let start = start % (len + 1);
+                let end = start + count % (len - start + 1);
+                let got: Vec<i64> = array.drain(start..end).collect();
+                let expected: Vec<i64> = oracle.drain(start..end).collect();
+                if got != expected {
+                    return differs(format!("drained {got:?}, expected {expected:?}"));
+                }
+            }
+            Op::Retain(byte) => {
+                array.retain(|x| *x != value(byte));
+                oracle.retain(|x| *x != value(byte));
+            }
+            Op::InsertMany(index, count) => {
+                let index = index % (len + 1);
+                let items = (0..count % 8).map(|i| value(i.wrapping_mul(count)));
+                array.insert_many(index, items.clone());
+                oracle.insert_many(index, items);
+            }
+            Op::Remove(_) | Op::Set(..) => {}
+        }
+        if array.size() != oracle.size() {
+            return differs(format!("size {}, expected {}", array.size(), oracle.size()));
+        }
+    }
+    if !array.iter().eq(oracle.iter()) {
+        let got: Vec<&i64> = array.iter().collect();
+        return Err(format!(
+            "contents {got:?}, expected {:?}",
+            oracle.iter().collect::<Vec<_>>()
+        ));
+    }
+    Ok(())
+}
+
+// A xorshift generator: reproducible from the seed and good enough to pick operations.
+#[cfg(test)]
+pub(crate) struct Rng(u64);
+
+#[cfg(test)]
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub(crate) fn op(&mut self) -> Op {
+        let (index, byte) = (self.below(4 * LEN), self.next_u64() as u8);
+        // Mostly growing operations, so the arrays get long enough to be interesting.
+        match self.below(16) {
+            0..=3 => Op::Push(byte),
+            4..=7 => Op::Insert(index, byte),
+            8..=9 => Op::Remove(index),
+            10 => Op::Get(index),
+            11 => Op::Set(index, byte),
+            12 => Op::Truncate(index),
+            13 => Op::Drain(index, self.below(LEN / 4)),
+            14 => Op::Retain(byte),
+            _ => Op::InsertMany(index, byte),
+        }
+    }
+}
+
+// Runs `cases` random sequences of up to `len` operations. A failing sequence is shrunk to
+// a minimal one before panicking with it, so the message is a ready-made reproduction.
+#[cfg(test)]
+pub(crate) fn differential<A>(factory: impl Fn() -> A, seed: u64, cases: usize, len: usize)
+where
+    A: IArray<i64> + IIterable<i64>,
+{
+    let mut rng = Rng::new(seed);
+    for case in 0..cases {
+        let ops: Vec<Op> = (0..rng.below(len) + 1).map(|_| rng.op()).collect();
+        if outcome(&factory, &ops).is_err() {
+            let minimal = shrink(ops, |ops| outcome(&factory, ops).is_err());
+            let error = outcome(&factory, &minimal).unwrap_err();
+            panic!("case {case} of seed {seed} fails: {error}\nminimal sequence: {minimal:?}");
+        }
+    }
+}
+
+// A panic in the container counts as a failure too.
+#[cfg(test)]
+fn outcome<A>(factory: impl Fn() -> A, ops: &[Op]) -> Result<(), String>
+where
+    A: IArray<i64> + IIterable<i64>,
+{
+    panic::catch_unwind(AssertUnwindSafe(|| check_ops(&mut factory(), ops))).unwrap_or_else(
+        |payload| {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("unknown panic");
+            Err(format!("panicked: {message}"))
+        },
+    )
+}
+
+// Drops chunks of operations, then simplifies the remaining ones, while `fails` holds.
+#[cfg(test)]
+pub(crate) fn shrink(mut ops: Vec<Op>, fails: impl Fn(&[Op]) -> bool) -> Vec<Op> {
+    loop {
+        let before = ops.clone();
+        let mut chunk = ops.len() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start < ops.len() {
+                let mut candidate = ops.clone();
+                candidate.drain(start..(start + chunk).min(ops.len()));
+                if fails(&candidate) {
+                    ops = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        for i in 0..ops.len() {
+            for simpler in simplify(ops[i]) {
+                let mut candidate = ops.clone();
+                candidate[i] = simpler;
+                if fails(&candidate) {
+                    ops = candidate;
+                    break;
+                }
+            }
+        }
+        if ops == before {
+            return ops;
+        }
+    }
+}
+
+#[cfg(test)]
+fn simplify(op: Op) -> Vec<Op> {
+    let smaller = |n: usize| [0, n / 2].into_iter().filter(move |&m| m < n);
+    let zero = |byte: u8| (byte != 0).then_some(0);
+    match op {
+        Op::Push(byte) => zero(byte).map(Op::Push).into_iter().collect(),
+        Op::Insert(index, byte) => smaller(index)
+            .map(|index| Op::Insert(index, byte))
+            .chain(zero(byte).map(|byte| Op::Insert(index, byte)))
+            .chain([Op::Push(byte)])
+            .collect(),
+        Op::Remove(index) => smaller(index).map(Op::Remove).collect(),
+        Op::Get(index) => smaller(index).map(Op::Get).collect(),
+        Op::Set(index, byte) => smaller(index).map(|index| Op::Set(index, byte)).collect(),
+        Op::Truncate(len) => smaller(len).map(Op::Truncate).collect(),
+        Op::Drain(start, count) => smaller(start)
+            .map(|start| Op::Drain(start, count))
+            .chain(smaller(count).map(|count| Op::Drain(start, count)))
+            .collect(),
+        Op::Retain(byte) => zero(byte).map(Op::Retain).into_iter().collect(),
+        Op::InsertMany(index, count) => smaller(index)
+            .map(|index| Op::InsertMany(index, count))
+            .chain(smaller(usize::from(count)).map(|count| Op::InsertMany(index, count as u8)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        array::Array, matrix_array::MatrixArray, single_array::SingleArray,
+        sparse_array::SparseArray, vector_array::VectorArray,
+    };
+
+    const CASES: usize = 200;
+
+    #[test]
+    fn raw_arrays_match_vec() {
+        differential(Array::new, 1, CASES, 60);
+        differential(SingleArray::new, 2, CASES, 60);
+        differential(|| VectorArray::new(3), 3, CASES, 60);
+    }
+
+    #[test]
+    fn matrix_array_matches_vec() {
+        for vector in [1, 2, 3, 5, 8, 16] {
+            differential(|| MatrixArray::new(vector), vector as u64, CASES, 80);
+        }
+        differential(MatrixArray::adaptive, 9, CASES, 80);
+    }
+
+    #[test]
+    fn sparse_array_matches_vec() {
+        differential(SparseArray::new, 4, CASES, 80);
+    }
+
+    #[test]
+    fn shrinks_to_a_minimal_sequence() {
+        // Fails as soon as an insert lands at the front of a non-empty array.
+        let ops = vec![
+            Op::Push(1),
+            Op::Get(3),
+            Op::Push(2),
+            Op::Insert(6, 9),
+            Op::Remove(1),
+            Op::Insert(3, 4),
+        ];
+        let fails = |ops: &[Op]| {
+            let mut len = 0;
+            ops.iter().any(|op| match *op {
+                Op::Push(_) => {
+                    len += 1;
+                    false
+                }
+                Op::Insert(index, _) => {
+                    len += 1;
+                    len > 1 && index % len == 0
+                }
+                _ => false,
+            })
+        };
+        assert_eq!(vec![Op::Push(0), Op::Insert(0, 0)], shrink(ops, fails));
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    alloc::{self, Layout},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryReserveError {
+    // The requested capacity exceeds `isize::MAX` bytes or overflows `usize`.
+    CapacityOverflow,
+    // The allocator returned null for this layout.
+    AllocError { layout: Layout },
+}
+
+impl TryReserveError {
+    // What the infallible API does with the error: panic on overflow, abort on OOM.
+    pub(crate) fn handle(self) -> ! {
+        match self {
+            TryReserveError::CapacityOverflow => panic!("capacity overflow"),
+            TryReserveError::AllocError { layout } => alloc::handle_alloc_error(layout),
+        }
+    }
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => write!(f, "capacity overflow"),
+            TryReserveError::AllocError { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexError {
+    pub index: usize,
+    pub len: usize,
+}
+
+impl IndexError {
+    pub fn new(index: usize, len: usize) -> Self {
+        Self { index, len }
+    }
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "index out of bounds: the len is {} but the index is {}",
+            self.len, self.index
+        )
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+// Why an insert that checks both its index and its allocation failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertError {
+    Index(IndexError),
+    Reserve(TryReserveError),
+}
+
+impl From<IndexError> for InsertError {
+    fn from(err: IndexError) -> Self {
+        InsertError::Index(err)
+    }
+}
+
+impl From<TryReserveError> for InsertError {
+    fn from(err: TryReserveError) -> Self {
+        InsertError::Reserve(err)
+    }
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::Index(err) => err.fmt(f),
+            InsertError::Reserve(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for InsertError {}
@@ -0,0 +1,166 @@
+// Fuzz harnesses: each one decodes arbitrary bytes into an operation stream, runs it on one
+// container and panics when the result differs from the oracle. The `cargo fuzz` targets in
+// `fuzz/` call these, and the tests below replay the seed corpus in `fuzz/corpus/`.
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    array::Array,
+    differential::{self, Op},
+    matrix_array::MatrixArray,
+    priority_queue::PriorityQueue,
+    single_array::SingleArray,
+    sparse_array::SparseArray,
+    vector_array::VectorArray,
+    IArray, IIterable,
+};
+
+// Every operation is a tag byte followed by its arguments; indices take two bytes, values
+// one. Missing bytes at the end of the input read as zero, so any input decodes.
+pub(crate) fn decode(data: &[u8]) -> Vec<Op> {
+    let mut bytes = data.iter().copied();
+    let mut ops = Vec::new();
+    while let Some(tag) = bytes.next() {
+        let mut byte = || bytes.next().unwrap_or(0);
+        let op = match tag % 9 {
+            0 => Op::Push(byte()),
+            1 => Op::Insert(usize::from(u16::from_le_bytes([byte(), byte()])), byte()),
+            2 => Op::Remove(usize::from(u16::from_le_bytes([byte(), byte()]))),
+            3 => Op::Get(usize::from(u16::from_le_bytes([byte(), byte()]))),
+            4 => Op::Set(usize::from(u16::from_le_bytes([byte(), byte()])), byte()),
+            5 => Op::Truncate(usize::from(u16::from_le_bytes([byte(), byte()]))),
+            6 => Op::Drain(
+                usize::from(u16::from_le_bytes([byte(), byte()])),
+                usize::from(byte()),
+            ),
+            7 => Op::Retain(byte()),
+            _ => Op::InsertMany(usize::from(u16::from_le_bytes([byte(), byte()])), byte()),
+        };
+        ops.push(op);
+    }
+    ops
+}
+
+fn run<A>(mut array: A, data: &[u8])
+where
+    A: IArray<i64> + IIterable<i64>,
+{
+    if let Err(error) = differential::check_ops(&mut array, &decode(data)) {
+        panic!("{error}");
+    }
+}
+
+pub fn array(data: &[u8]) {
+    run(Array::new(), data);
+}
+
+pub fn single_array(data: &[u8]) {
+    run(SingleArray::new(), data);
+}
+
+pub fn vector_array(data: &[u8]) {
+    run(VectorArray::new(5), data);
+}
+
+// The first byte picks the block size, mostly tiny ones, where blocks overflow constantly.
+pub fn matrix_array(data: &[u8]) {
+    let Some((&vector, data)) = data.split_first() else {
+        return;
+    };
+    let vector = match vector % 4 {
+        3 => usize::from(vector / 4) + 1,
+        small => usize::from(small) + 1,
+    };
+    run(MatrixArray::new(vector), data);
+}
+
+// Values are reduced to `0..4` by `Op`, so about a quarter of them are default elements.
+pub fn sparse_array(data: &[u8]) {
+    run(SparseArray::new(), data);
+}
+
+// Pairs of bytes enqueue an item with a priority in `-8..8`; a zero tag dequeues instead.
+// The oracle orders by the lowest priority first, and the newest item among equal ones.
+pub fn priority_queue(data: &[u8]) {
+    let mut queue = PriorityQueue::new();
+    let mut oracle = BinaryHeap::new();
+    for (seq, pair) in data.chunks(2).enumerate() {
+        let (tag, item) = (pair[0], pair.get(1).copied().unwrap_or(0));
+        if tag == 0 {
+            let expected = oracle.pop().map(|(_, _, item)| item);
+            assert_eq!(expected, queue.dequeue(), "step {seq}: dequeue");
+        } else {
+            let priority = i64::from(tag % 16) - 8;
+            queue.enqueue(priority, item);
+            oracle.push((Reverse(priority), seq, item));
+        }
+    }
+    let expected: Vec<u8> = oracle
+        .into_sorted_vec()
+        .into_iter()
+        .rev()
+        .map(|(_, _, item)| item)
+        .collect();
+    assert_eq!(
+        expected,
+        queue.into_iter().collect::<Vec<_>>(),
+        "remaining items"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::differential::Rng;
+
+    type Harness = fn(&[u8]);
+
+    const TARGETS: [(&str, Harness); 6] = [
+        ("array", array),
+        ("single_array", single_array),
+        ("vector_array", vector_array),
+        ("matrix_array", matrix_array),
+        ("sparse_array", sparse_array),
+        ("priority_queue", priority_queue),
+    ];
+
+    #[test]
+    fn replay_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        for (target, harness) in TARGETS {
+            let mut entries: Vec<_> = fs::read_dir(corpus.join(target))
+                .unwrap_or_else(|error| panic!("corpus for {target}: {error}"))
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            entries.sort();
+            assert!(!entries.is_empty(), "empty corpus for {target}");
+            for path in entries {
+                let data = fs::read(&path).unwrap();
+                if std::panic::catch_unwind(|| harness(&data)).is_err() {
+                    panic!("{} fails", path.display());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_inputs() {
+        let mut rng = Rng::new(19);
+        for (_, harness) in TARGETS {
+            for _ in 0..100 {
+                let data: Vec<u8> = (0..rng.below(400)).map(|_| rng.next_u64() as u8).collect();
+                harness(&data);
+            }
+        }
+    }
+
+    #[test]
+    fn decode() {
+        assert!(super::decode(&[]).is_empty());
+        assert_eq!(
+            vec![Op::Push(7), Op::Insert(0x0102, 3), Op::Drain(5, 0)],
+            super::decode(&[0, 7, 1, 2, 1, 3, 6, 5])
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// How a `RawArray` picks its next capacity once the current one is exhausted.
+// `required` is the smallest capacity that fits; a smaller answer is rounded up to it.
+pub trait GrowthPolicy {
+    fn grow(&self, cap: usize, required: usize) -> usize;
+
+    // The capacity to shrink to once `len` elements are left, if any. Used only when
+    // auto shrinking is enabled; the gap to the next `grow` keeps push/pop from thrashing.
+    fn shrink(&self, _cap: usize, _len: usize) -> Option<usize> {
+        None
+    }
+}
+
+// Multiplies the capacity, `Array` uses the classic doubling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Factor {
+    factor: f64,
+}
+
+impl Factor {
+    pub fn new(factor: f64) -> Self {
+        assert!(factor > 1.0, "growth factor must be greater than 1");
+        Self { factor }
+    }
+}
+
+impl Default for Factor {
+    fn default() -> Self {
+        Self { factor: 2.0 }
+    }
+}
+
+impl GrowthPolicy for Factor {
+    fn grow(&self, cap: usize, required: usize) -> usize {
+        // Float to int casts saturate, the allocation itself reports the overflow.
+        let grown = (cap as f64 * self.factor).ceil() as usize;
+        grown.max(cap.saturating_add(1)).max(required)
+    }
+
+    // Divides by the factor once only 1/factor² of the buffer is used: halving at a quarter.
+    fn shrink(&self, cap: usize, len: usize) -> Option<usize> {
+        let threshold = (cap as f64 / (self.factor * self.factor)) as usize;
+        (len < threshold).then(|| (cap as f64 / self.factor) as usize)
+    }
+}
+
+// Adds a fixed number of slots, as `VectorArray` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Linear {
+    step: usize,
+}
+
+impl Linear {
+    pub fn new(step: usize) -> Self {
+        Self { step }
+    }
+
+    pub fn step(&self) -> usize {
+        self.step
+    }
+}
+
+impl Default for Linear {
+    fn default() -> Self {
+        Self { step: 5 }
+    }
+}
+
+impl GrowthPolicy for Linear {
+    fn grow(&self, cap: usize, required: usize) -> usize {
+        cap.saturating_add(self.step).max(required)
+    }
+
+    // Gives back one step once two steps are unused.
+    fn shrink(&self, cap: usize, len: usize) -> Option<usize> {
+        (self.step > 0 && cap - len >= self.step.saturating_mul(2)).then(|| cap - self.step)
+    }
+}
+
+// Allocates exactly what is needed, as `SingleArray` does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Exact;
+
+impl GrowthPolicy for Exact {
+    fn grow(&self, _cap: usize, required: usize) -> usize {
+        required
+    }
+
+    // Once half of the buffer is unused, trims it to half as much again as the length plus
+    // one slot. The slack means a push right after shrinking, and a pop after that, never
+    // reallocate, down to a bounce between zero and one elements.
+    fn shrink(&self, cap: usize, len: usize) -> Option<usize> {
+        let target = len + len / 2 + 1;
+        (len <= cap / 2 && target < cap).then_some(target)
+    }
+}
+
+// Walks the Fibonacci sequence: grows by a factor approaching the golden ratio.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fibonacci;
+
+impl GrowthPolicy for Fibonacci {
+    fn grow(&self, cap: usize, required: usize) -> usize {
+        let (mut prev, mut next) = (1_usize, 2_usize);
+        while next <= cap {
+            match prev.checked_add(next) {
+                Some(sum) => (prev, next) = (next, sum),
+                None => return usize::MAX,
+            }
+        }
+        next.max(required)
+    }
+
+    // Steps one number back once the length drops below the one before it: 13 -> 8 below 5.
+    fn shrink(&self, cap: usize, len: usize) -> Option<usize> {
+        let (mut older, mut prev, mut next) = (1_usize, 1_usize, 2_usize);
+        while next < cap {
+            (older, prev, next) = (prev, next, prev.saturating_add(next));
+        }
+        (len < older).then_some(prev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capacities(policy: &impl GrowthPolicy, steps: usize) -> Vec<usize> {
+        let mut cap = 0;
+        (0..steps)
+            .map(|_| {
+                cap = policy.grow(cap, cap + 1);
+                cap
+            })
+            .collect()
+    }
+
+    #[test]
+    fn built_in_policies() {
+        assert_eq!(vec![1, 2, 4, 8, 16], capacities(&Factor::default(), 5));
+        assert_eq!(vec![1, 2, 3, 5, 8, 12], capacities(&Factor::new(1.5), 6));
+        assert_eq!(vec![10, 20, 30], capacities(&Linear::new(10), 3));
+        assert_eq!(vec![1, 2, 3, 4], capacities(&Exact, 4));
+        assert_eq!(vec![2, 3, 5, 8, 13], capacities(&Fibonacci, 5));
+        assert_eq!(100, Exact.grow(3, 100));
+        assert_eq!(100, Factor::default().grow(3, 100));
+    }
+
+    #[test]
+    fn shrink_rules() {
+        assert_eq!(None, Factor::default().shrink(16, 4));
+        assert_eq!(Some(8), Factor::default().shrink(16, 3));
+        assert_eq!(Some(8), Factor::new(1.5).shrink(12, 4));
+        assert_eq!(None, Linear::new(10).shrink(30, 11));
+        assert_eq!(Some(20), Linear::new(10).shrink(30, 10));
+        assert_eq!(None, Linear::new(usize::MAX).shrink(usize::MAX, 1));
+        assert_eq!(None, Exact.shrink(4, 3));
+        assert_eq!(None, Exact.shrink(4, 2));
+        assert_eq!(Some(2), Exact.shrink(4, 1));
+        assert_eq!(Some(76), Exact.shrink(100, 50));
+        assert_eq!(None, Exact.shrink(2, 1));
+        assert_eq!(None, Exact.shrink(1, 0));
+        assert_eq!(None, Exact.shrink(4, 4));
+        assert_eq!(None, Fibonacci.shrink(13, 5));
+        assert_eq!(Some(8), Fibonacci.shrink(13, 4));
+    }
+}
@@ -1,20 +1,198 @@
+pub mod allocator;
 pub mod array;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+#[cfg(any(test, feature = "fuzz"))]
+mod differential;
+pub mod error;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+pub mod growth;
 pub mod list;
 pub mod matrix_array;
 pub mod priority_queue;
+pub mod raw_array;
+mod raw_buf;
+mod ring;
 pub mod single_array;
 pub mod sparse_array;
+#[cfg(feature = "stats")]
+pub mod stats;
 pub mod vector_array;
 
+use std::{
+    alloc::Layout,
+    hash::{Hash, Hasher},
+    mem,
+    ops::{Bound, RangeBounds},
+    slice, vec,
+};
+
+use error::{IndexError, TryReserveError};
+
 pub trait IArray<T> {
     fn size(&self) -> usize;
     fn push(&mut self, elem: T); // analog of void add(T item)
     fn get(&self, index: usize) -> &T;
     fn insert(&mut self, elem: T, index: usize); // analog of void add(T item, int index)
     fn remove(&mut self, index: usize) -> T;
+
+    // Fallible counterparts of the methods above: they report allocation failure instead of aborting.
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>;
+    fn try_push(&mut self, elem: T) -> Result<(), TryReserveError> {
+        self.try_insert(elem, self.size())
+    }
+    fn try_insert(&mut self, elem: T, index: usize) -> Result<(), TryReserveError>;
+
+    // Non-panicking access: `None` or `IndexError` when `index` is out of bounds.
+    fn try_get(&self, index: usize) -> Option<&T> {
+        if index < self.size() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+    fn try_remove(&mut self, index: usize) -> Result<T, IndexError> {
+        if index < self.size() {
+            Ok(self.remove(index))
+        } else {
+            Err(IndexError::new(index, self.size()))
+        }
+    }
+
+    // Replaces the element at `index` and returns the old one.
+    fn set(&mut self, index: usize, elem: T) -> T {
+        let len = self.size();
+        match self.get_mut(index) {
+            Some(current) => mem::replace(current, elem),
+            None => panic!("{}", IndexError::new(index, len)),
+        }
+    }
+
+    // Bulk removal in one pass instead of a `remove` per element. The defaults move the
+    // elements through a `Vec`; the raw arrays compact in place. If a callback panics the
+    // defaults drop the elements still in the `Vec`: nothing leaks, but they are lost.
+    fn truncate(&mut self, len: usize) {
+        while self.size() > len {
+            self.remove(self.size() - 1);
+        }
+    }
+    fn clear(&mut self) {
+        self.truncate(0);
+    }
+    fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F)
+    where
+        Self: Sized,
+    {
+        self.retain_mut(|elem| keep(elem));
+    }
+    fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, keep: F)
+    where
+        Self: Sized,
+    {
+        let mut elems = take_from(self, 0);
+        elems.retain_mut(keep);
+        elems.into_iter().for_each(|elem| self.push(elem));
+    }
+    // Keeps the first of every run of elements with equal keys.
+    fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F)
+    where
+        Self: Sized,
+    {
+        let mut last = None;
+        self.retain_mut(|elem| {
+            let current = Some(key(elem));
+            let keep = current != last;
+            last = current;
+            keep
+        });
+    }
+    // Removes the range and returns it; unlike `RawArray::drain` this is eager.
+    fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> vec::IntoIter<T>
+    where
+        Self: Sized,
+    {
+        let (start, end) = range_bounds(range, self.size());
+        let mut drained = take_from(self, start);
+        let tail = drained.split_off(end - start);
+        tail.into_iter().for_each(|elem| self.push(elem));
+        drained.into_iter()
+    }
+
+    // Inserts all items before `index`, shifting the tail once rather than once per item.
+    fn insert_many<I: IntoIterator<Item = T>>(&mut self, index: usize, items: I)
+    where
+        Self: Sized,
+    {
+        assert!(index <= self.size(), "index out of bounds");
+        let tail = take_from(self, index);
+        items.into_iter().for_each(|elem| self.push(elem));
+        tail.into_iter().for_each(|elem| self.push(elem));
+    }
+}
+
+// Removes everything from `start` on, back to front so no element is shifted.
+fn take_from<T>(array: &mut impl IArray<T>, start: usize) -> Vec<T> {
+    let mut taken = Vec::with_capacity(array.size().saturating_sub(start));
+    while array.size() > start {
+        taken.push(array.remove(array.size() - 1));
+    }
+    taken.reverse();
+    taken
+}
+
+// Resolves a range against `len` with the same panics as slice indexing.
+fn range_bounds(range: impl RangeBounds<usize>, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1).expect("range start overflows usize"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1).expect("range end overflows usize"),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range starts at {start} but ends at {end}");
+    assert!(end <= len, "range end {end} out of bounds for length {len}");
+    (start, end)
 }
 
-#[derive(Debug, Default)]
+// Kept apart from `IArray` so that `dyn IArray<T>` stays usable.
+pub trait IIterable<T> {
+    type Iter<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+// `SparseArray` has no storage behind its implicit zeros, so it can only be `IIterable`.
+pub trait IIterableMut<T>: IIterable<T> {
+    type IterMut<'a>: Iterator<Item = &'a mut T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_>;
+}
+
+// The `Hash` shared by all containers: the length, then every element in order.
+fn hash_sequence<'a, T, I, S>(iter: I, state: &mut S)
+where
+    T: Hash + 'a,
+    I: ExactSizeIterator<Item = &'a T>,
+    S: Hasher,
+{
+    state.write_usize(iter.len());
+    for elem in iter {
+        elem.hash(state);
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Wrapper<T> {
     inner: Vec<T>,
 }
@@ -38,6 +216,14 @@ impl<T> IArray<T> for Wrapper<T> {
         self.inner.get(index).unwrap()
     }
 
+    fn try_get(&self, index: usize) -> Option<&T> {
+        self.inner.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.inner.get_mut(index)
+    }
+
     fn insert(&mut self, elem: T, index: usize) {
         self.inner.insert(index, elem);
     }
@@ -45,4 +231,67 @@ impl<T> IArray<T> for Wrapper<T> {
     fn remove(&mut self, index: usize) -> T {
         self.inner.remove(index)
     }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional).map_err(|_| {
+            // The kind of `std::collections::TryReserveError` is not public, so recover it.
+            match self
+                .inner
+                .len()
+                .checked_add(additional)
+                .and_then(|required| Layout::array::<T>(required).ok())
+            {
+                Some(layout) => TryReserveError::AllocError { layout },
+                None => TryReserveError::CapacityOverflow,
+            }
+        })
+    }
+
+    fn try_insert(&mut self, elem: T, index: usize) -> Result<(), TryReserveError> {
+        self.try_reserve(1)?;
+        self.inner.insert(index, elem);
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.inner.truncate(len);
+    }
+
+    fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, keep: F) {
+        self.inner.retain_mut(keep);
+    }
+
+    fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, key: F) {
+        self.inner.dedup_by_key(key);
+    }
+
+    fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> vec::IntoIter<T> {
+        self.inner.drain(range).collect::<Vec<_>>().into_iter()
+    }
+
+    fn insert_many<I: IntoIterator<Item = T>>(&mut self, index: usize, items: I) {
+        self.inner.splice(index..index, items);
+    }
+}
+
+impl<T> IIterable<T> for Wrapper<T> {
+    type Iter<'a>
+        = slice::Iter<'a, T>
+    where
+        T: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.inner.iter()
+    }
+}
+
+impl<T> IIterableMut<T> for Wrapper<T> {
+    type IterMut<'a>
+        = slice::IterMut<'a, T>
+    where
+        T: 'a;
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        self.inner.iter_mut()
+    }
 }
@@ -1,25 +1,54 @@
-use std::fmt::Debug;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    mem,
+    ops::{Index, IndexMut},
+    slice,
+};
 
-use crate::{array::Array, vector_array::VectorArray, IArray};
+use crate::{
+    allocator::RawAllocator,
+    array::Array,
+    error::{IndexError, InsertError, TryReserveError},
+    raw_array::{self, RawArray},
+    ring::{self, Ring},
+    sparse_array::SparseArray,
+    IArray, IIterable, IIterableMut,
+};
 
-#[derive(Debug)]
-pub struct MatrixArray<T: Debug> {
-    inner: Array<VectorArray<T>>,
+#[cfg(feature = "stats")]
+use crate::stats::{self, Event};
+
+// A tiered vector: the elements live in ring buffers of `vector` slots each, all of them
+// full except the last one. Element `i` is at position `i % vector` of block `i / vector`,
+// so access is O(1). Inserting or removing shifts only within one block and then carries
+// a single element across each later block, from the back of one to the front of the
+// next, which is O(1) per block; with `vector` near sqrt(n) both are O(sqrt(n)).
+// An adaptive array keeps it there by rebuilding the blocks whenever the length leaves
+// `vector² / 4..=vector² * 4`, which costs O(1) amortized per insert or remove.
+pub struct MatrixArray<T> {
+    inner: Array<Ring<T>>,
     size: usize,
     vector: usize,
+    adaptive: bool,
 }
 
-impl<T: Debug> Default for MatrixArray<T> {
+// The smallest block size an adaptive array uses, so that short ones are not all overhead.
+const MIN_ADAPTIVE_VECTOR: usize = 8;
+
+impl<T> Default for MatrixArray<T> {
     fn default() -> Self {
         Self {
             inner: Default::default(),
             size: 0,
             vector: 5,
+            adaptive: false,
         }
     }
 }
 
-impl<T: Debug> IArray<T> for MatrixArray<T> {
+impl<T> IArray<T> for MatrixArray<T> {
     fn size(&self) -> usize {
         self.size
     }
@@ -29,67 +58,232 @@ impl<T: Debug> IArray<T> for MatrixArray<T> {
     }
 
     fn get(&self, index: usize) -> &T {
+        match self.try_get(index) {
+            Some(elem) => elem,
+            None => panic!("{}", IndexError::new(index, self.size)),
+        }
+    }
+
+    fn try_get(&self, index: usize) -> Option<&T> {
+        if index >= self.size {
+            return None;
+        }
+        let (inner_index, single_index) = self.make_indice(index);
+        self.inner.try_get(inner_index)?.get(single_index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.size {
+            return None;
+        }
         let (inner_index, single_index) = self.make_indice(index);
-        let store = self.inner.get(inner_index);
-        store.get(single_index)
+        self.inner.get_mut(inner_index)?.get_mut(single_index)
     }
 
+    // Like `Array`: `index == size` appends, anything past that panics before any change.
     fn insert(&mut self, elem: T, index: usize) {
-        self.make_room();
+        self.check_index(index, self.size + 1);
+        if let Err(err) = self.try_make_room() {
+            err.handle();
+        }
         let (inner_index, single_index) = self.make_indice(index);
-        let store = self.inner.get_mut(inner_index).unwrap();
-        store.insert(elem, single_index);
+        // The last block has room now, so every carry frees a slot in the block before it.
+        // Nothing here allocates or runs code of `T`, so nothing can panic halfway.
+        for matrix_index in (inner_index + 1..self.inner.size()).rev() {
+            let carried = self.inner[matrix_index - 1].pop_back().unwrap();
+            self.inner[matrix_index].push_front(carried);
+        }
+        self.carried(self.inner.size() - 1 - inner_index);
+        self.inner[inner_index].insert(single_index, elem);
         self.size += 1;
-        if store.size() > self.vector {
-            let last_elem = store.remove(store.size() - 1);
-            self.insert(last_elem, (inner_index + 1) * self.vector);
+        if self.adaptive && self.size > self.vector.saturating_mul(self.vector).saturating_mul(4) {
+            self.adapt();
         }
     }
 
     fn remove(&mut self, index: usize) -> T {
+        self.check_index(index, self.size);
         let (inner_index, single_index) = self.make_indice(index);
-        let store = self.inner.get_mut(inner_index).unwrap();
-        let removed = store.remove(single_index);
+        let removed = self.inner[inner_index].remove(single_index);
         self.shift_after_remove(inner_index);
         self.check_last_and_remove_if_empty();
         self.size -= 1;
+        if self.adaptive
+            && self.vector > MIN_ADAPTIVE_VECTOR
+            && self.size < self.vector.saturating_mul(self.vector) / 4
+        {
+            self.adapt();
+        }
         removed
     }
+
+    // Only reserves slots in the block table; storage for a block is allocated when it is created.
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let required = self
+            .size
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let blocks = required.div_ceil(self.vector);
+        self.inner
+            .try_reserve(blocks.saturating_sub(self.inner.size()))
+    }
+
+    // Panics on a bad index like `Array`; `try_insert_at` reports it instead.
+    fn try_insert(&mut self, elem: T, index: usize) -> Result<(), TryReserveError> {
+        self.check_index(index, self.size + 1);
+        self.try_make_room()?;
+        self.insert(elem, index);
+        Ok(())
+    }
 }
 
-impl<T: Debug> MatrixArray<T> {
+impl<T> MatrixArray<T> {
     pub fn new(vector: usize) -> Self {
+        assert!(vector > 0, "a block must hold at least one element");
         Self {
             vector,
             ..Default::default()
         }
     }
 
-    pub fn repr(&self) -> String {
-        let mut result: Vec<String> = vec![String::from("[")];
-        for i in 0..self.inner.size() {
-            result.push(format!("{:?},", self.inner.get(i) as &[T]));
+    // An array whose block size follows the square root of its length.
+    pub fn adaptive() -> Self {
+        Self {
+            vector: MIN_ADAPTIVE_VECTOR,
+            adaptive: true,
+            ..Default::default()
+        }
+    }
+
+    // Never panics: a bad index or a failed allocation is returned and the array is left as
+    // it was. Once the last block has room, inserting never allocates.
+    pub fn try_insert_at(&mut self, elem: T, index: usize) -> Result<(), InsertError> {
+        if index > self.size {
+            return Err(IndexError::new(index, self.size).into());
+        }
+        self.try_make_room()?;
+        self.insert(elem, index);
+        Ok(())
+    }
+
+    // The number of elements per block.
+    pub fn vector(&self) -> usize {
+        self.vector
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.adaptive
+    }
+
+    pub fn set_adaptive(&mut self, adaptive: bool) {
+        self.adaptive = adaptive;
+        if adaptive {
+            self.adapt();
+        }
+    }
+
+    // Splits or merges the blocks so that their size is near the square root of the length.
+    pub fn rebalance(&mut self) {
+        if let Err(err) = self.try_rebalance() {
+            err.handle();
+        }
+    }
+
+    // On failure the blocks are left as they were.
+    pub fn try_rebalance(&mut self) -> Result<(), TryReserveError> {
+        let vector = self.size.isqrt().max(MIN_ADAPTIVE_VECTOR);
+        if vector == self.vector {
+            return Ok(());
+        }
+        let mut inner = Array::new();
+        inner.try_reserve(self.size.div_ceil(vector))?;
+        for _ in 0..self.size.div_ceil(vector) {
+            inner.push(Ring::with_capacity(vector)?);
+        }
+        // Every block is allocated, so moving the elements over cannot fail.
+        let mut blocks = inner.iter_mut();
+        let mut target = blocks.next();
+        for block in self.inner.iter_mut() {
+            while let Some(elem) = block.pop_front() {
+                let ring = target.as_mut().unwrap();
+                ring.push_back(elem);
+                if ring.is_full() {
+                    target = blocks.next();
+                }
+            }
+        }
+        self.carried(self.size);
+        self.inner = inner;
+        self.vector = vector;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        (self.size > 0).then(|| self.remove(self.size - 1))
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.size.checked_sub(1).map(|index| self.get(index))
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        let index = self.size.checked_sub(1)?;
+        self.get_mut(index)
+    }
+
+    // One item per block, in order. A block is a ring buffer, so its elements come as two
+    // slices like `VecDeque::as_slices`; the second one is empty unless the ring wraps around.
+    pub fn blocks(&self) -> impl Iterator<Item = (&[T], &[T])> {
+        self.inner.iter().map(Ring::as_slices)
+    }
+
+    // One slice per block: each ring is first rotated so that its elements are contiguous.
+    pub fn blocks_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.inner.iter_mut().map(Ring::make_contiguous)
+    }
+
+    // The elements as consecutive slices, for processing them without indexing. The slices do
+    // not map one to one onto blocks: a block whose ring wraps around gives two of them, and
+    // empty ones are skipped.
+    pub fn as_slices(&self) -> impl Iterator<Item = &[T]> {
+        self.inner
+            .iter()
+            .flat_map(|block| <[&[T]; 2]>::from(block.as_slices()))
+            .filter(|slice| !slice.is_empty())
+    }
+
+    pub fn as_mut_slices(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.inner
+            .iter_mut()
+            .flat_map(|block| <[&mut [T]; 2]>::from(block.as_mut_slices()))
+            .filter(|slice| !slice.is_empty())
+    }
+
+    // Rebalancing on the fly is best effort: on failure the current blocks are simply kept.
+    fn adapt(&mut self) {
+        let _ = self.try_rebalance();
+    }
+
+    fn check_index(&self, index: usize, end: usize) {
+        if index >= end {
+            panic!("{}", IndexError::new(index, self.size));
         }
-        result.push(String::from("]"));
-        result.join("\n")
     }
 
     fn make_indice(&self, index: usize) -> (usize, usize) {
         (index / self.vector, index % self.vector)
     }
 
-    fn make_room(&mut self) {
+    fn try_make_room(&mut self) -> Result<(), TryReserveError> {
         if self.need_to_grow() {
-            self.inner.push(VectorArray::new(self.vector));
+            let block = Ring::with_capacity(self.vector)?;
+            self.inner.try_push(block)?;
         }
+        Ok(())
     }
 
     fn need_to_grow(&self) -> bool {
-        if let Some(last) = self.inner.last() {
-            last.size() >= self.vector
-        } else {
-            true
-        }
+        self.inner.last().is_none_or(Ring::is_full)
     }
 
     fn check_last_and_remove_if_empty(&mut self) {
@@ -103,17 +297,295 @@ impl<T: Debug> MatrixArray<T> {
 
     fn shift_after_remove(&mut self, inner_index: usize) {
         for matrix_index in (inner_index + 1)..self.inner.size() {
-            let shifted = self.inner[matrix_index].remove(0);
-            let prev = self.inner.get_mut(matrix_index - 1).unwrap();
-            prev.push(shifted);
+            let shifted = self.inner[matrix_index].pop_front().unwrap();
+            self.inner[matrix_index - 1].push_back(shifted);
+        }
+        self.carried(self.inner.size() - 1 - inner_index);
+    }
+
+    // Counts the elements carried from one block into another when the `stats` feature is on.
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn carried(&self, count: usize) {
+        #[cfg(feature = "stats")]
+        stats::record_global(Event::Moved {
+            bytes: count * mem::size_of::<T>(),
+        });
+    }
+}
+
+impl<T: Debug> MatrixArray<T> {
+    pub fn repr(&self) -> String {
+        let mut result: Vec<String> = vec![String::from("[")];
+        for i in 0..self.inner.size() {
+            result.push(format!("{:?},", self.inner.get(i)));
+        }
+        result.push(String::from("]"));
+        result.join("\n")
+    }
+}
+
+impl<T> Index<usize> for MatrixArray<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index)
+    }
+}
+
+impl<T> IndexMut<usize> for MatrixArray<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let size = self.size;
+        match self.get_mut(index) {
+            Some(elem) => elem,
+            None => panic!("{}", IndexError::new(index, size)),
+        }
+    }
+}
+
+impl<T> IIterable<T> for MatrixArray<T> {
+    type Iter<'a>
+        = Iter<'a, T>
+    where
+        T: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        Iter {
+            blocks: self.inner.iter(),
+            current: [].iter(),
+            wrapped: &[],
+            remaining: self.size,
+        }
+    }
+}
+
+impl<T> IIterableMut<T> for MatrixArray<T> {
+    type IterMut<'a>
+        = IterMut<'a, T>
+    where
+        T: 'a;
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        IterMut {
+            blocks: self.inner.iter_mut(),
+            current: [].iter_mut(),
+            wrapped: &mut [],
+            remaining: self.size,
+        }
+    }
+}
+
+// Walks the matrix block by block, so every step is a plain slice iteration. A block is
+// one or two slices, depending on where its ring wraps around; `wrapped` is the second.
+pub struct Iter<'a, T> {
+    blocks: slice::Iter<'a, Ring<T>>,
+    current: slice::Iter<'a, T>,
+    wrapped: &'a [T],
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(elem) = self.current.next() {
+                self.remaining -= 1;
+                return Some(elem);
+            }
+            if self.wrapped.is_empty() {
+                let (first, second) = self.blocks.next()?.as_slices();
+                self.current = first.iter();
+                self.wrapped = second;
+            } else {
+                self.current = mem::take(&mut self.wrapped).iter();
+            }
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+pub struct IterMut<'a, T> {
+    blocks: slice::IterMut<'a, Ring<T>>,
+    current: slice::IterMut<'a, T>,
+    wrapped: &'a mut [T],
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(elem) = self.current.next() {
+                self.remaining -= 1;
+                return Some(elem);
+            }
+            if self.wrapped.is_empty() {
+                let (first, second) = self.blocks.next()?.as_mut_slices();
+                self.current = first.iter_mut();
+                self.wrapped = second;
+            } else {
+                self.current = mem::take(&mut self.wrapped).iter_mut();
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+// Rebuilt element by element, so the clone gets the usual block layout.
+impl<T: Clone> Clone for MatrixArray<T> {
+    fn clone(&self) -> Self {
+        let mut array = Self::new(self.vector);
+        array.adaptive = self.adaptive;
+        array.extend(self.iter().cloned());
+        array
+    }
+}
+
+impl<T: Debug> Debug for MatrixArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
 }
 
+// Equal to any container holding the same sequence, whatever the block size.
+impl<T: PartialEq<U>, U> PartialEq<MatrixArray<U>> for MatrixArray<T> {
+    fn eq(&self, other: &MatrixArray<U>) -> bool {
+        self.size == other.size && self.iter().eq(other.iter())
+    }
+}
+
+impl<T, U, G, A> PartialEq<RawArray<U, G, A>> for MatrixArray<T>
+where
+    T: PartialEq<U>,
+    A: RawAllocator,
+{
+    fn eq(&self, other: &RawArray<U, G, A>) -> bool {
+        self.size == other.len() && self.iter().eq(other[..].iter())
+    }
+}
+
+impl<T, U, G, A> PartialEq<MatrixArray<U>> for RawArray<T, G, A>
+where
+    T: PartialEq<U>,
+    A: RawAllocator,
+{
+    fn eq(&self, other: &MatrixArray<U>) -> bool {
+        self.len() == other.size && self[..].iter().eq(other.iter())
+    }
+}
+
+impl<T, U> PartialEq<SparseArray<U>> for MatrixArray<T>
+where
+    T: PartialEq<U>,
+    U: Default + PartialEq,
+{
+    fn eq(&self, other: &SparseArray<U>) -> bool {
+        self.size == other.size() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for MatrixArray<T> {}
+
+impl<T: PartialOrd> PartialOrd for MatrixArray<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord> Ord for MatrixArray<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Hash> Hash for MatrixArray<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        crate::hash_sequence(self.iter(), state);
+    }
+}
+
+impl<T> IntoIterator for MatrixArray<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            blocks: self.inner.into_iter(),
+            current: None,
+            remaining: self.size,
+        }
+    }
+}
+
+impl<T> FromIterator<T> for MatrixArray<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut array = Self::default();
+        array.extend(iter);
+        array
+    }
+}
+
+impl<T> Extend<T> for MatrixArray<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        if let Err(err) = self.try_reserve(iter.size_hint().0) {
+            err.handle();
+        }
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+// Unyielded elements are dropped together with the blocks that own them.
+pub struct IntoIter<T> {
+    blocks: raw_array::IntoIter<Ring<T>>,
+    current: Option<ring::IntoIter<T>>,
+    remaining: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(elem) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(elem);
+            }
+            self.current = Some(self.blocks.next()?.into_iter());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
 #[cfg(test)]
 mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
     use super::*;
 
+    crate::conformance_tests!(conformance_single, || MatrixArray::new(1), |i| i as i64);
+    crate::conformance_tests!(conformance_small, || MatrixArray::new(3), |i| i.to_string());
+    crate::conformance_tests!(conformance_large, || MatrixArray::new(64), |i| i as i64);
+    crate::conformance_tests!(conformance_adaptive, MatrixArray::adaptive, |i| i as i64);
+
     #[test]
     fn creation() {
         let mut bytes = MatrixArray::<u8>::new(100);
@@ -152,4 +624,368 @@ mod tests {
         assert_eq!(6, bytes.remove(6));
         println!("{}", bytes.repr());
     }
+
+    #[test]
+    fn front_inserts_into_a_large_array() {
+        // A hundred thousand blocks: carrying through them must not grow the stack.
+        let len = 1_000_000;
+        let mut array = MatrixArray::<u32>::new(10);
+        (0..len).for_each(|x| array.push(x));
+        for x in 0..20 {
+            array.insert(len + x, 0);
+        }
+        assert_eq!(len as usize + 20, array.size());
+        assert_eq!(&(len + 19), array.get(0));
+        assert_eq!(&0, array.get(20));
+        assert_eq!(&(len - 1), array.get(array.size() - 1));
+        for x in (0..20).rev() {
+            assert_eq!(len + x, array.remove(0));
+        }
+        assert!(array.iter().copied().eq(0..len));
+    }
+
+    #[test]
+    fn adaptive_block_size() {
+        let mut array = MatrixArray::adaptive();
+        let mut oracle = Vec::new();
+        for x in 0..10_000 {
+            array.insert(x, x as usize / 2);
+            oracle.insert(x as usize / 2, x);
+        }
+        // Within a factor of two of sqrt(10_000).
+        assert!((50..=200).contains(&array.vector()), "{}", array.vector());
+        assert!(array.iter().eq(oracle.iter()));
+        while array.size() > 30 {
+            let index = array.size() / 3;
+            assert_eq!(oracle.remove(index), array.remove(index));
+        }
+        assert_eq!(MIN_ADAPTIVE_VECTOR, array.vector());
+        assert!(array.iter().eq(oracle.iter()));
+        assert!(array.clone().is_adaptive());
+    }
+
+    #[test]
+    fn rebalance() {
+        let mut array: MatrixArray<i64> = (0..2_500).collect();
+        assert_eq!(5, array.vector());
+        array.rebalance();
+        assert_eq!(50, array.vector());
+        assert!(array.iter().copied().eq(0..2_500));
+        array.insert(-1, 0);
+        array.truncate(10);
+        array.rebalance();
+        assert_eq!(MIN_ADAPTIVE_VECTOR, array.vector());
+        assert!(array.iter().copied().eq((-1..9).collect::<Vec<_>>()));
+        // Fixed arrays keep whatever block size they were given or rebalanced to.
+        (0..10_000).for_each(|x| array.push(x));
+        assert_eq!(MIN_ADAPTIVE_VECTOR, array.vector());
+        array.set_adaptive(true);
+        assert_eq!(100, array.vector());
+    }
+
+    #[test]
+    fn fallible_insert() {
+        let mut array = MatrixArray::<i64>::new(3);
+        assert_eq!(
+            Err(TryReserveError::CapacityOverflow),
+            array.try_reserve(usize::MAX)
+        );
+        for i in 0..10 {
+            assert!(array.try_push(i).is_ok());
+        }
+        assert!(array.try_insert(42, 0).is_ok());
+        assert_eq!(11, array.size());
+        assert_eq!(&42, array.get(0));
+        assert_eq!(&9, array.get(10));
+    }
+
+    #[test]
+    fn checked_access() {
+        let mut array = MatrixArray::<i64>::new(3);
+        (0..7).for_each(|x| array.push(x));
+        assert_eq!(Some(&6), array.try_get(6));
+        assert_eq!(None, array.try_get(7));
+        assert_eq!(None, array.try_get(8));
+        *array.get_mut(4).unwrap() = 42;
+        assert!(array.get_mut(7).is_none());
+        assert_eq!(5, array.set(5, -5));
+        assert_eq!(&42, array.get(4));
+        assert_eq!(&-5, array.get(5));
+        assert_eq!(Err(IndexError::new(7, 7)), array.try_remove(7));
+        assert_eq!(Ok(0), array.try_remove(0));
+        assert_eq!(&42, array.get(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "the len is 7 but the index is 8")]
+    fn get_out_of_bounds() {
+        let mut array = MatrixArray::<i64>::new(3);
+        (0..7).for_each(|x| array.push(x));
+        array.get(8);
+    }
+
+    #[test]
+    fn mutable_access() {
+        let mut array: MatrixArray<i64> = (0..10).collect();
+        array[3] = 30;
+        *array.last_mut().unwrap() += 90;
+        assert_eq!(30, array[3]);
+        assert_eq!(Some(&99), array.last());
+        assert_eq!(Some(99), array.pop());
+        assert_eq!(Some(&8), array.last());
+        let rest: Vec<i64> = std::iter::from_fn(|| array.pop()).collect();
+        assert_eq!(vec![8, 7, 6, 5, 4, 30, 2, 1, 0], rest);
+        assert_eq!(None, array.pop());
+        assert_eq!(None, array.last());
+        assert_eq!(None, array.last_mut());
+    }
+
+    #[test]
+    #[should_panic(expected = "the len is 3 but the index is 3")]
+    fn index_mut_out_of_bounds() {
+        let mut array: MatrixArray<i64> = (0..3).collect();
+        array[3] = 0;
+    }
+
+    #[test]
+    fn as_slices() {
+        let mut array = MatrixArray::new(4);
+        (0..10).for_each(|x| array.push(x));
+        assert_eq!(
+            vec![&[0, 1, 2, 3][..], &[4, 5, 6, 7], &[8, 9]],
+            array.as_slices().collect::<Vec<_>>()
+        );
+        // A front insert wraps every full ring around, so each of the three blocks gives two.
+        array.insert(-1, 0);
+        let slices: Vec<&[i32]> = array.as_slices().collect();
+        assert_eq!(3, array.inner.size());
+        assert_eq!(6, slices.len());
+        assert_eq!(array.size(), slices.iter().map(|slice| slice.len()).sum());
+        assert!(slices.concat().iter().eq(array.iter()));
+        array
+            .as_mut_slices()
+            .for_each(|slice| slice.sort_by(|a, b| b.cmp(a)));
+        assert!(array
+            .as_slices()
+            .all(|slice| slice.is_sorted_by(|a, b| a >= b)));
+        assert_eq!(0, MatrixArray::<i32>::new(4).as_slices().count());
+    }
+
+    #[test]
+    fn blocks() {
+        let mut array = MatrixArray::new(4);
+        (0..10).for_each(|x| array.push(x));
+        array.insert(-1, 0);
+        let blocks: Vec<_> = array.blocks().collect();
+        assert_eq!(
+            vec![
+                (&[-1][..], &[0, 1, 2][..]),
+                (&[3][..], &[4, 5, 6][..]),
+                (&[7][..], &[8, 9][..]),
+            ],
+            blocks
+        );
+        let slices: Vec<&mut [i32]> = array.blocks_mut().collect();
+        assert_eq!(3, slices.len());
+        slices.into_iter().for_each(|block| block.reverse());
+        assert_eq!(
+            vec![(&[2, 1, 0, -1][..], &[][..]), (&[6, 5, 4, 3][..], &[][..])],
+            array.blocks().take(2).collect::<Vec<_>>()
+        );
+        assert!(array.iter().copied().eq([2, 1, 0, -1, 6, 5, 4, 3, 9, 8, 7]));
+        array.push(10);
+        assert_eq!(&[9, 8, 7, 10], array.blocks_mut().last().unwrap());
+        assert_eq!(0, MatrixArray::<i32>::new(4).blocks().count());
+    }
+
+    #[test]
+    fn elements_without_debug() {
+        #[derive(Clone, PartialEq)]
+        struct Opaque(u8);
+
+        let mut array: MatrixArray<Opaque> = (0..10).map(Opaque).collect();
+        array.insert(Opaque(42), 5);
+        array[0] = Opaque(7);
+        assert!(array.clone() == array);
+        assert_eq!(42, array.remove(5).0);
+        assert_eq!(9, array.pop().unwrap().0);
+        assert_eq!(
+            vec![7, 1, 2, 3, 4, 5, 6, 7, 8],
+            array.into_iter().map(|x| x.0).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn insert_at_every_edge() {
+        for vector in [1, 2, 3, 4] {
+            let mut array = MatrixArray::new(vector);
+            let mut oracle = Vec::new();
+            array.insert(0, 0);
+            oracle.insert(0, 0);
+            for x in 1..20 {
+                // Alternately the end of the last block and a block boundary.
+                let index = if x % 2 == 0 {
+                    oracle.len()
+                } else {
+                    oracle.len() / vector / 2 * vector
+                };
+                array.insert(x, index);
+                oracle.insert(index, x);
+                assert!(array.iter().eq(oracle.iter()), "vector {vector}");
+            }
+            // The last element, a block start, the front and the last slot of a full block.
+            for kind in 0..4 {
+                let len = oracle.len();
+                let index = [len - 1, vector, 0, (len - 1) / vector * vector - 1][kind];
+                assert_eq!(oracle.remove(index), array.remove(index), "vector {vector}");
+            }
+            assert!(array.iter().eq(oracle.iter()), "vector {vector}");
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_changes_nothing() {
+        let mut array: MatrixArray<i64> = (0..7).collect();
+        for (index, len) in [(8, 7), (9, 7), (100, 7)] {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| array.insert(-1, index)));
+            let message = *result.unwrap_err().downcast::<String>().unwrap();
+            assert_eq!(IndexError::new(index, len).to_string(), message);
+        }
+        for index in [7, 8] {
+            assert!(panic::catch_unwind(AssertUnwindSafe(|| array.remove(index))).is_err());
+            assert_eq!(Err(IndexError::new(index, 7)), array.try_remove(index));
+        }
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| array.try_insert(-1, 8))).is_err());
+        assert!(array.iter().copied().eq(0..7));
+        for index in [8, 100] {
+            assert_eq!(
+                Err(InsertError::Index(IndexError::new(index, 7))),
+                array.try_insert_at(-1, index)
+            );
+        }
+        assert!(array.iter().copied().eq(0..7));
+        // Appending needs a new block, which the failed calls must not have left behind.
+        array.insert(7, 7);
+        assert_eq!(2, array.inner.size());
+    }
+
+    #[test]
+    #[should_panic(expected = "the len is 0 but the index is 0")]
+    fn remove_from_empty() {
+        let mut array = MatrixArray::<i64>::new(3);
+        assert_eq!(Err(IndexError::new(0, 0)), array.try_remove(0));
+        array.remove(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the len is 0 but the index is 1")]
+    fn insert_into_empty() {
+        let mut array = MatrixArray::<i64>::new(3);
+        assert_eq!(
+            Err(InsertError::Index(IndexError::new(5, 0))),
+            array.try_insert_at(1, 5)
+        );
+        assert_eq!(0, array.inner.size());
+        assert_eq!(Ok(()), array.try_insert_at(1, 0));
+        assert_eq!(Some(1), array.pop());
+        array.insert(0, 1);
+    }
+
+    #[test]
+    fn iteration() {
+        let mut array = MatrixArray::<i64>::new(3);
+        (0..10).for_each(|x| array.push(x));
+        array.insert(42, 4);
+        let mut iter = array.iter();
+        assert_eq!(11, iter.len());
+        iter.next();
+        assert_eq!(10, iter.len());
+        assert_eq!(
+            vec![0, 1, 2, 3, 42, 4, 5, 6, 7, 8, 9],
+            array.iter().copied().collect::<Vec<_>>()
+        );
+        array.iter_mut().for_each(|x| *x *= 2);
+        assert_eq!(&84, array.get(4));
+        assert_eq!(&18, array.get(10));
+        assert_eq!(0, MatrixArray::<i64>::new(3).iter().count());
+    }
+
+    #[test]
+    fn owning_iteration() {
+        let mut array: MatrixArray<String> = (0..10).map(|x| x.to_string()).collect();
+        array.extend(["a", "b"].map(String::from));
+        assert_eq!(12, array.size());
+        assert_eq!("a", array.get(10));
+        let mut iter = array.into_iter();
+        assert_eq!(12, iter.len());
+        assert_eq!(Some(String::from("0")), iter.next());
+        assert_eq!(Some(String::from("2")), iter.nth(1));
+        assert_eq!(9, iter.len());
+        drop(iter);
+        let array: MatrixArray<String> = ["x", "y"].map(String::from).into_iter().collect();
+        assert_eq!(vec!["x", "y"], array.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn clone_and_compare() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash_of(value: &impl Hash) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let array: MatrixArray<u8> = (0..10).collect();
+        let mut other = MatrixArray::new(3);
+        (0..10).for_each(|x| other.push(x));
+        assert_eq!(array, other);
+        assert_eq!(hash_of(&array), hash_of(&other));
+        let copy = other.clone();
+        assert_eq!(other, copy);
+        let flat: Array<u8> = (0..10).collect();
+        assert_eq!(array, flat);
+        assert_eq!(flat, array);
+        assert_eq!(hash_of(&array), hash_of(&flat));
+        let sparse: SparseArray<u8> = (0..10).collect();
+        assert_eq!(hash_of(&array), hash_of(&sparse));
+        other.remove(9);
+        assert!(other < array);
+        assert_eq!("[0, 1, 2, 3, 4, 5, 6, 7, 8]", format!("{:?}", other));
+    }
+
+    #[test]
+    fn bulk_removal() {
+        let mut array: MatrixArray<i32> = (0..20).collect();
+        array.retain(|x| x % 3 != 0);
+        assert_eq!(13, array.size());
+        assert_eq!(
+            vec![1, 2, 4, 5, 7],
+            array.iter().copied().take(5).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![4, 5, 7], array.drain(2..5).collect::<Vec<_>>());
+        assert_eq!(
+            vec![1, 2, 8, 10, 11],
+            array.iter().copied().take(5).collect::<Vec<_>>()
+        );
+        array.dedup_by_key(|x| *x / 10);
+        assert_eq!(vec![1, 10], array.iter().copied().collect::<Vec<_>>());
+        array.truncate(1);
+        assert_eq!(vec![1], array.iter().copied().collect::<Vec<_>>());
+        array.clear();
+        assert_eq!(0, array.size());
+        array.push(7);
+        assert_eq!(&7, array.get(0));
+    }
+
+    #[test]
+    fn bulk_insertion() {
+        let mut array: MatrixArray<i32> = (0..8).collect();
+        array.insert_many(3, 100..105);
+        assert_eq!(13, array.size());
+        assert_eq!(
+            vec![0, 1, 2, 100, 101, 102, 103, 104, 3, 4, 5, 6, 7],
+            array.iter().copied().collect::<Vec<_>>()
+        );
+    }
 }
@@ -1,6 +1,10 @@
+use std::{cmp::Ordering, fmt};
+
 use crate::{array::Array, IArray};
 
-#[derive(Default)]
+// Two queues are equal when they would dequeue the same items with the same priorities;
+// they are ordered by those (priority, item) pairs, compared in dequeue order.
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
 pub struct PriorityQueue<T> {
     queue: Array<Priority<T>>,
 }
@@ -26,6 +30,18 @@ impl<T> PriorityQueue<T> {
         result
     }
 
+    // The items with their priorities, in the order `dequeue` would return them.
+    fn entries(&self) -> impl Iterator<Item = (i64, &T)> {
+        self.queue.iter().rev().flat_map(|current| {
+            current
+                .stack
+                .inner
+                .iter()
+                .rev()
+                .map(|item| (current.priority, item))
+        })
+    }
+
     fn find_priority_or_insert_new(&mut self, p: i64) -> &mut Priority<T> {
         match self.find_priority_pos(p) {
             Some(position) => &mut self.queue[position],
@@ -54,6 +70,67 @@ impl<T> PriorityQueue<T> {
     }
 }
 
+impl<T: PartialOrd> PartialOrd for PriorityQueue<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.entries().partial_cmp(other.entries())
+    }
+}
+
+impl<T: Ord> Ord for PriorityQueue<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entries().cmp(other.entries())
+    }
+}
+
+// Lists the items in dequeue order.
+impl<T: fmt::Debug> fmt::Debug for PriorityQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.entries().map(|(_, item)| item))
+            .finish()
+    }
+}
+
+// Yields the items in the order `dequeue` would return them.
+impl<T> IntoIterator for PriorityQueue<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { queue: self }
+    }
+}
+
+impl<T> FromIterator<(i64, T)> for PriorityQueue<T> {
+    fn from_iter<I: IntoIterator<Item = (i64, T)>>(iter: I) -> Self {
+        let mut queue = Self::new();
+        queue.extend(iter);
+        queue
+    }
+}
+
+// The number of distinct priorities is unknown, so there is nothing to reserve up front.
+impl<T> Extend<(i64, T)> for PriorityQueue<T> {
+    fn extend<I: IntoIterator<Item = (i64, T)>>(&mut self, iter: I) {
+        for (priority, item) in iter {
+            self.enqueue(priority, item);
+        }
+    }
+}
+
+pub struct IntoIter<T> {
+    queue: PriorityQueue<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.dequeue()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Priority<T> {
     pub priority: i64,
     pub stack: Stack<T>,
@@ -68,6 +145,7 @@ impl<T> Priority<T> {
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Stack<T> {
     inner: Array<T>,
 }
@@ -122,4 +200,33 @@ mod tests {
         }
         assert_eq!(vec![6, 4, 7, 3, 5, 2, 1], deq);
     }
+
+    #[test]
+    fn owning_iteration() {
+        let mut deque: PriorityQueue<&str> = [(1, "a"), (-1, "b")].into_iter().collect();
+        deque.extend([(0, "c"), (1, "d")]);
+        assert_eq!(
+            vec!["b", "c", "d", "a"],
+            deque.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn clone_and_compare() {
+        let deque: PriorityQueue<u8> = [(1, 1), (0, 42), (1, 2)].into_iter().collect();
+        let mut copy = deque.clone();
+        assert_eq!(deque, copy);
+        copy.dequeue();
+        assert_ne!(deque, copy);
+        assert_eq!("[42, 2, 1]", format!("{:?}", deque));
+        assert_eq!("[]", format!("{:?}", PriorityQueue::<u8>::new()));
+        // Lowest priority first, like `dequeue`: the copy starts with 2 at priority 1.
+        assert!(copy > deque);
+        let mut other: PriorityQueue<u8> = [(1, 1), (0, 42)].into_iter().collect();
+        assert!(other < deque);
+        other.enqueue(1, 2);
+        assert_eq!(Some(Ordering::Equal), deque.partial_cmp(&other));
+        assert_eq!(deque, other);
+        assert!(deque > [(-1, u8::MAX)].into_iter().collect());
+    }
 }
@@ -0,0 +1,1403 @@
+use std::{
+    cmp,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    ops::{Deref, DerefMut, RangeBounds},
+    ptr, slice, vec,
+};
+
+#[cfg(feature = "stats")]
+use crate::stats::{self, Event, Stats};
+use crate::{
+    allocator::{Global, RawAllocator},
+    error::TryReserveError,
+    growth::{Factor, GrowthPolicy},
+    range_bounds,
+    raw_buf::RawBuf,
+    IArray, IIterable, IIterableMut,
+};
+
+/// The storage behind `Array`, `SingleArray` and `VectorArray`: they only differ in `G`.
+/// Memory comes from `A`, the global allocator unless another one is given. The elements
+/// are stored contiguously and the array dereferences to a slice of them; zero-sized
+/// elements never allocate.
+///
+/// # Panic safety
+/// A panic in a `Drop`, `Clone`, callback or iterator of `T` never causes a double drop or
+/// a read of a moved-out slot, and the buffer is always freed. Single element operations
+/// leave the array untouched when they panic. Bulk operations keep every element that was
+/// not handed to the callback or dropped yet, in order; only the range being worked on may
+/// lose elements, which are then dropped rather than leaked.
+///
+/// # Thread safety
+/// It is `Send` and `Sync` exactly when `T` and `A` are, so neither of these compiles:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<array_list::array::Array<std::rc::Rc<i32>>>();
+/// ```
+///
+/// ```compile_fail
+/// use array_list::{allocator::Arena, growth::Factor, raw_array::RawArray};
+/// fn assert_send<T: Send>() {}
+/// assert_send::<RawArray<i32, Factor, &Arena>>();
+/// ```
+pub struct RawArray<T, G = Factor, A: RawAllocator = Global> {
+    // The first `len` slots are initialized, the rest are not; all unsafe code relies on that.
+    buf: RawBuf<T, A>,
+    len: usize,
+    growth: G,
+    auto_shrink: bool,
+    #[cfg(feature = "stats")]
+    stats: Stats,
+}
+
+impl<T, G: GrowthPolicy + Default, A: RawAllocator + Default> Default for RawArray<T, G, A> {
+    fn default() -> Self {
+        Self::with_growth_in(G::default(), A::default())
+    }
+}
+
+impl<T, G, A: RawAllocator> Drop for RawArray<T, G, A> {
+    fn drop(&mut self) {
+        #[cfg(feature = "stats")]
+        if self.buf.is_allocated() {
+            self.stats.record(Event::Deallocated);
+        }
+        // The buffer is freed by its own `Drop`, also when dropping an element panics.
+        unsafe { self.buf.drop_range(0, self.len) };
+    }
+}
+
+impl<T, G, A: RawAllocator> Deref for RawArray<T, G, A> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        unsafe { self.buf.slice(0, self.len) }
+    }
+}
+
+impl<T, G, A: RawAllocator> DerefMut for RawArray<T, G, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { self.buf.slice_mut(0, self.len) }
+    }
+}
+
+impl<T, G> RawArray<T, G> {
+    pub fn with_growth(growth: G) -> Self {
+        Self::with_growth_in(growth, Global)
+    }
+
+    pub fn with_capacity_and_growth(capacity: usize, growth: G) -> Self {
+        Self::with_capacity_and_growth_in(capacity, growth, Global)
+    }
+}
+
+impl<T, G, A: RawAllocator> RawArray<T, G, A> {
+    pub fn with_growth_in(growth: G, alloc: A) -> Self {
+        RawArray {
+            buf: RawBuf::new_in(alloc),
+            len: 0,
+            growth,
+            auto_shrink: false,
+            #[cfg(feature = "stats")]
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn with_capacity_and_growth_in(capacity: usize, growth: G, alloc: A) -> Self {
+        let mut array = Self::with_growth_in(growth, alloc);
+        array.reserve_exact(capacity);
+        array
+    }
+
+    pub fn growth(&self) -> &G {
+        &self.growth
+    }
+
+    pub fn allocator(&self) -> &A {
+        self.buf.allocator()
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    // Like `Vec::as_ptr`: the pointer does not go through `Deref`, so pointers taken from it
+    // stay usable while the array is read, and written through `as_mut_ptr`, until it
+    // reallocates.
+    pub fn as_ptr(&self) -> *const T {
+        self.buf.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.buf.as_ptr()
+    }
+
+    // When enabled, `pop` and `remove` give memory back as `GrowthPolicy::shrink` suggests.
+    pub fn set_auto_shrink(&mut self, enabled: bool) {
+        self.auto_shrink = enabled;
+    }
+
+    pub fn auto_shrink(&self) -> bool {
+        self.auto_shrink
+    }
+
+    pub fn pop(&mut self) -> Option<T>
+    where
+        G: GrowthPolicy,
+    {
+        if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            let result = unsafe { self.buf.read(self.len) };
+            self.shrink_if_needed();
+            Some(result)
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize)
+    where
+        G: GrowthPolicy,
+    {
+        if let Err(err) = self.try_reserve(additional) {
+            err.handle();
+        }
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) {
+        if let Err(err) = self.try_reserve_exact(additional) {
+            err.handle();
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    // Capacity never drops below the length or the given bound; it is never increased.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let new_cap = cmp::max(self.len, min_capacity);
+        if new_cap < self.capacity() {
+            if let Err(err) = self.try_set_capacity(new_cap) {
+                err.handle();
+            }
+        }
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>
+    where
+        G: GrowthPolicy,
+    {
+        if self.capacity() - self.len >= additional {
+            return Ok(());
+        }
+        // For ZSTs the capacity is `usize::MAX`, so only an overflowing `len` gets here.
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let new_cap = cmp::max(self.growth.grow(self.capacity(), required), required);
+        self.try_set_capacity(new_cap)
+    }
+
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.capacity() - self.len >= additional {
+            return Ok(());
+        }
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        self.try_set_capacity(required)
+    }
+
+    // Never below `len`, so no initialized slot is lost.
+    fn try_set_capacity(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        debug_assert!(new_cap >= self.len);
+        #[cfg(feature = "stats")]
+        let was_allocated = self.buf.is_allocated();
+        self.buf.try_set_capacity(new_cap)?;
+        #[cfg(feature = "stats")]
+        match (was_allocated, self.buf.is_allocated()) {
+            (false, true) => self.stats.record(Event::Allocated { cap: new_cap }),
+            (true, true) => self.stats.record(Event::Reallocated { cap: new_cap }),
+            (true, false) => self.stats.record(Event::Deallocated),
+            (false, false) => {}
+        }
+        Ok(())
+    }
+
+    fn shrink_if_needed(&mut self)
+    where
+        G: GrowthPolicy,
+    {
+        if !self.auto_shrink || mem::size_of::<T>() == 0 {
+            return;
+        }
+        if let Some(new_cap) = self.growth.shrink(self.capacity(), self.len) {
+            if self.len <= new_cap && new_cap < self.capacity() {
+                // Shrinking is best effort: on failure the larger buffer is simply kept.
+                let _ = self.try_set_capacity(new_cap);
+            }
+        }
+    }
+
+    // Counts the elements shifted in the buffer when the `stats` feature is on.
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn moved(&mut self, count: usize) {
+        #[cfg(feature = "stats")]
+        self.stats.record(Event::Moved {
+            bytes: count * mem::size_of::<T>(),
+        });
+    }
+}
+
+// Bulk removal: every survivor is moved at most once.
+impl<T, G: GrowthPolicy, A: RawAllocator> RawArray<T, G, A> {
+    // Drops the elements past `len`, if there are any.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let old_len = self.len;
+        // The length goes first: a panicking `Drop` must not lead to a double drop.
+        self.len = len;
+        unsafe { self.buf.drop_range(len, old_len) };
+        self.shrink_if_needed();
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) {
+        self.retain_mut(|elem| keep(elem));
+    }
+
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut keep: F) {
+        let mut compact = Compact::new(self, 0);
+        while let Some(current) = compact.current() {
+            if keep(current) {
+                compact.keep();
+            } else {
+                compact.discard();
+            }
+        }
+    }
+
+    // Removes consecutive elements for which `same_bucket(current, previous)` is true.
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same_bucket: F) {
+        let mut compact = Compact::new(self, 1);
+        while let Some((current, previous)) = compact.current_and_last_kept() {
+            if same_bucket(current, previous) {
+                compact.discard();
+            } else {
+                compact.keep();
+            }
+        }
+    }
+
+    pub fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F) {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+
+    // Removes the range lazily; the tail is moved back once, when the `Drain` is dropped.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, G, A> {
+        let (start, end) = range_bounds(range, self.len);
+        let tail_len = self.len - end;
+        // Leaking the `Drain` leaks the range and the tail, but never exposes moved-out slots.
+        self.len = start;
+        Drain {
+            array: self,
+            next: start,
+            end,
+            tail_start: end,
+            tail_len,
+        }
+    }
+}
+
+// Bulk insertion: the tail is shifted once, or twice when `size_hint` undercounts.
+impl<T, G: GrowthPolicy, A: RawAllocator> RawArray<T, G, A> {
+    // Replaces `range` with the items and returns the removed elements.
+    pub fn splice<R, I>(&mut self, range: R, items: I) -> vec::IntoIter<T>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+    {
+        let (start, end) = range_bounds(range, self.len);
+        let tail_len = self.len - end;
+        let mut gap = Gap::new(self, start, end, tail_len);
+        let removed: Vec<T> = (start..end)
+            .map(|index| unsafe { gap.array.buf.read(index) })
+            .collect();
+        let mut items = items.into_iter();
+        if gap.fill(&mut items) {
+            gap.widen(items.size_hint().0);
+            if gap.fill(&mut items) {
+                let rest: Vec<T> = items.collect();
+                gap.widen(rest.len());
+                gap.fill(&mut rest.into_iter());
+            }
+        }
+        removed.into_iter()
+    }
+
+    pub fn insert_many<I: IntoIterator<Item = T>>(&mut self, index: usize, items: I) {
+        assert!(index <= self.len, "index out of bounds");
+        self.splice(index..index, items);
+    }
+
+    // Moves all elements of `other` to the end, leaving it empty with its capacity.
+    pub fn append<H, B: RawAllocator>(&mut self, other: &mut RawArray<T, H, B>) {
+        self.reserve(other.len);
+        let count = mem::take(&mut other.len);
+        self.moved(count);
+        unsafe { other.buf.move_to(0, &mut self.buf, self.len, count) };
+        self.len += count;
+    }
+
+    // Returns the elements from `at` on in a new array with the same growth and allocator.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        G: Clone,
+        A: Clone,
+    {
+        assert!(at <= self.len, "index out of bounds");
+        let count = self.len - at;
+        let mut other =
+            Self::with_capacity_and_growth_in(count, self.growth.clone(), self.allocator().clone());
+        other.auto_shrink = self.auto_shrink;
+        self.len = at;
+        self.moved(count);
+        unsafe { self.buf.move_to(at, &mut other.buf, 0, count) };
+        other.len = count;
+        self.shrink_if_needed();
+        other
+    }
+
+    // Reserves once and clones element by element; if a `clone` panics, the clones made so
+    // far stay in the array. `extend_from_copy_slice` copies `Copy` types in one go.
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        self.reserve(other.len());
+        let mut len = SetLenOnDrop {
+            local: self.len,
+            len: &mut self.len,
+        };
+        for elem in other {
+            unsafe { self.buf.write(len.local, elem.clone()) };
+            len.local += 1;
+        }
+    }
+
+    // A single `memcpy`, which cannot panic halfway.
+    pub fn extend_from_copy_slice(&mut self, other: &[T])
+    where
+        T: Copy,
+    {
+        self.reserve(other.len());
+        unsafe { self.buf.copy_from_slice(self.len, other) };
+        self.len += other.len();
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> IArray<T> for RawArray<T, G, A> {
+    fn size(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, elem: T) {
+        self.insert(elem, self.len);
+    }
+
+    fn get(&self, index: usize) -> &T {
+        (self as &[T]).get(index).unwrap()
+    }
+
+    fn try_get(&self, index: usize) -> Option<&T> {
+        (self as &[T]).get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        (self as &mut [T]).get_mut(index)
+    }
+
+    fn insert(&mut self, elem: T, index: usize) {
+        if let Err(err) = self.try_insert(elem, index) {
+            err.handle();
+        }
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        Self::try_reserve(self, additional)
+    }
+
+    fn try_insert(&mut self, elem: T, index: usize) -> Result<(), TryReserveError> {
+        assert!(index <= self.len, "index out of bounds");
+        self.try_reserve(1)?;
+        self.moved(self.len - index);
+        unsafe {
+            self.buf.shift(index, index + 1, self.len - index);
+            self.buf.write(index, elem);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        // Note: `<` because it's *not* valid to remove after everything
+        assert!(index < self.len, "index out of bounds");
+        self.moved(self.len - index - 1);
+        self.len -= 1;
+        let result = unsafe {
+            let result = self.buf.read(index);
+            self.buf.shift(index + 1, index, self.len - index);
+            result
+        };
+        self.shrink_if_needed();
+        result
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Self::truncate(self, len);
+    }
+
+    fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, keep: F) {
+        Self::retain_mut(self, keep);
+    }
+
+    fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, key: F) {
+        Self::dedup_by_key(self, key);
+    }
+
+    fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> vec::IntoIter<T> {
+        Self::drain(self, range).collect::<Vec<_>>().into_iter()
+    }
+
+    fn insert_many<I: IntoIterator<Item = T>>(&mut self, index: usize, items: I) {
+        Self::insert_many(self, index, items);
+    }
+}
+
+impl<T, G, A: RawAllocator> IIterable<T> for RawArray<T, G, A> {
+    type Iter<'a>
+        = slice::Iter<'a, T>
+    where
+        T: 'a,
+        G: 'a,
+        A: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        <[T]>::iter(self)
+    }
+}
+
+impl<T, G, A: RawAllocator> IIterableMut<T> for RawArray<T, G, A> {
+    type IterMut<'a>
+        = slice::IterMut<'a, T>
+    where
+        T: 'a,
+        G: 'a,
+        A: 'a;
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        <[T]>::iter_mut(self)
+    }
+}
+
+impl<T: Clone, G: Clone, A: RawAllocator + Clone> Clone for RawArray<T, G, A> {
+    fn clone(&self) -> Self {
+        let mut array = Self::with_capacity_and_growth_in(
+            self.len,
+            self.growth.clone(),
+            self.allocator().clone(),
+        );
+        array.auto_shrink = self.auto_shrink;
+        for elem in self.iter() {
+            // The capacity is already there, so this is just a write.
+            unsafe { array.buf.write(array.len, elem.clone()) };
+            array.len += 1;
+        }
+        array
+    }
+}
+
+impl<T: fmt::Debug, G, A: RawAllocator> fmt::Debug for RawArray<T, G, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// Comparisons look at the elements only, never at the capacity or the policy.
+impl<T, U, G, H, A, B> PartialEq<RawArray<U, H, B>> for RawArray<T, G, A>
+where
+    T: PartialEq<U>,
+    A: RawAllocator,
+    B: RawAllocator,
+{
+    fn eq(&self, other: &RawArray<U, H, B>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<T: Eq, G, A: RawAllocator> Eq for RawArray<T, G, A> {}
+
+impl<T, G, H, A, B> PartialOrd<RawArray<T, H, B>> for RawArray<T, G, A>
+where
+    T: PartialOrd,
+    A: RawAllocator,
+    B: RawAllocator,
+{
+    fn partial_cmp(&self, other: &RawArray<T, H, B>) -> Option<Ordering> {
+        self[..].partial_cmp(&other[..])
+    }
+}
+
+impl<T: Ord, G, A: RawAllocator> Ord for RawArray<T, G, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self[..].cmp(&other[..])
+    }
+}
+
+// Hashed element by element, so every container holding the same sequence agrees.
+impl<T: Hash, G, A: RawAllocator> Hash for RawArray<T, G, A> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        crate::hash_sequence(self.iter(), state);
+    }
+}
+
+impl<T, G, A: RawAllocator> IntoIterator for RawArray<T, G, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+
+    fn into_iter(self) -> IntoIter<T, A> {
+        // The buffer now belongs to the iterator, so `RawArray::drop` must not run.
+        let array = mem::ManuallyDrop::new(self);
+        drop(unsafe { ptr::read(&array.growth) });
+        IntoIter {
+            buf: unsafe { ptr::read(&array.buf) },
+            start: 0,
+            end: array.len,
+        }
+    }
+}
+
+impl<T, G: GrowthPolicy + Default, A: RawAllocator + Default> FromIterator<T>
+    for RawArray<T, G, A>
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut array = Self::default();
+        array.extend(iter);
+        array
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> Extend<T> for RawArray<T, G, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+// The slots in `start..end` are the elements not yielded yet.
+pub struct IntoIter<T, A: RawAllocator = Global> {
+    buf: RawBuf<T, A>,
+    start: usize,
+    end: usize,
+}
+
+impl<T, A: RawAllocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            None
+        } else {
+            self.start += 1;
+            unsafe { Some(self.buf.read(self.start - 1)) }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.start;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, A: RawAllocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            None
+        } else {
+            self.end -= 1;
+            unsafe { Some(self.buf.read(self.end)) }
+        }
+    }
+}
+
+impl<T, A: RawAllocator> ExactSizeIterator for IntoIter<T, A> {}
+
+impl<T, A: RawAllocator> Drop for IntoIter<T, A> {
+    fn drop(&mut self) {
+        #[cfg(feature = "stats")]
+        if self.buf.is_allocated() {
+            stats::record_global(Event::Deallocated);
+        }
+        let (start, end) = (self.start, self.end);
+        self.start = end;
+        // The buffer is freed by its own `Drop`, also when dropping an element panics.
+        unsafe { self.buf.drop_range(start, end) };
+    }
+}
+
+// A hole in the middle of the array that `splice` fills: `filled..gap_end` is
+// uninitialized and the tail follows it. The array reports only the prefix meanwhile, and
+// dropping the guard moves the tail up to `filled`, also when the iterator panics.
+struct Gap<'a, T, G: GrowthPolicy, A: RawAllocator> {
+    array: &'a mut RawArray<T, G, A>,
+    filled: usize,
+    gap_end: usize,
+    tail_len: usize,
+}
+
+impl<'a, T, G: GrowthPolicy, A: RawAllocator> Gap<'a, T, G, A> {
+    fn new(array: &'a mut RawArray<T, G, A>, start: usize, end: usize, tail_len: usize) -> Self {
+        array.len = start;
+        Self {
+            array,
+            filled: start,
+            gap_end: end,
+            tail_len,
+        }
+    }
+
+    // Writes items until the gap is full; true if it is and more items may follow.
+    fn fill(&mut self, items: &mut impl Iterator<Item = T>) -> bool {
+        while self.filled < self.gap_end {
+            match items.next() {
+                Some(item) => {
+                    unsafe { self.array.buf.write(self.filled, item) };
+                    self.filled += 1;
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn widen(&mut self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+        let start = self.array.len;
+        // `reserve` counts from the length, so for a moment it has to cover the tail too.
+        self.array.len = self.gap_end + self.tail_len;
+        self.array.reserve(additional);
+        self.array.len = start;
+        unsafe {
+            self.array
+                .buf
+                .shift(self.gap_end, self.gap_end + additional, self.tail_len)
+        };
+        self.array.moved(self.tail_len);
+        self.gap_end += additional;
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> Drop for Gap<'_, T, G, A> {
+    fn drop(&mut self) {
+        if self.filled != self.gap_end {
+            unsafe {
+                self.array
+                    .buf
+                    .shift(self.gap_end, self.filled, self.tail_len)
+            };
+            self.array.moved(self.tail_len);
+        }
+        self.array.len = self.filled + self.tail_len;
+        self.array.shrink_if_needed();
+    }
+}
+
+// Writes the length back once, or at the point a `clone` panicked.
+struct SetLenOnDrop<'a> {
+    len: &'a mut usize,
+    local: usize,
+}
+
+impl Drop for SetLenOnDrop<'_> {
+    fn drop(&mut self) {
+        *self.len = self.local;
+    }
+}
+
+// A single compaction pass: `read` walks the array and survivors are moved down to `write`.
+// The array reports length 0 meanwhile, and dropping the guard closes the gap, so a panic
+// in a callback or in `Drop` leaves it consistent.
+struct Compact<'a, T, G: GrowthPolicy, A: RawAllocator> {
+    array: &'a mut RawArray<T, G, A>,
+    read: usize,
+    write: usize,
+    len: usize,
+    moved: usize,
+}
+
+impl<'a, T, G: GrowthPolicy, A: RawAllocator> Compact<'a, T, G, A> {
+    // The first `skip` elements are kept as they are.
+    fn new(array: &'a mut RawArray<T, G, A>, skip: usize) -> Self {
+        let len = array.len;
+        let skip = cmp::min(skip, len);
+        array.len = 0;
+        Self {
+            array,
+            read: skip,
+            write: skip,
+            len,
+            moved: 0,
+        }
+    }
+
+    fn current(&mut self) -> Option<&mut T> {
+        (self.read < self.len).then(|| unsafe { self.array.buf.get_mut(self.read) })
+    }
+
+    // `write - 1` is the last survivor, always before `read`.
+    fn current_and_last_kept(&mut self) -> Option<(&mut T, &mut T)> {
+        (self.read < self.len)
+            .then(|| unsafe { self.array.buf.get_pair_mut(self.read, self.write - 1) })
+    }
+
+    fn keep(&mut self) {
+        if self.read != self.write {
+            unsafe { self.array.buf.shift(self.read, self.write, 1) };
+            self.moved += 1;
+        }
+        self.read += 1;
+        self.write += 1;
+    }
+
+    fn discard(&mut self) {
+        self.read += 1;
+        unsafe { self.array.buf.drop_range(self.read - 1, self.read) };
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> Drop for Compact<'_, T, G, A> {
+    fn drop(&mut self) {
+        // Only non-empty after a panic: the unvisited elements are kept.
+        let unvisited = self.len - self.read;
+        if unvisited > 0 && self.read != self.write {
+            unsafe { self.array.buf.shift(self.read, self.write, unvisited) };
+            self.moved += unvisited;
+        }
+        self.array.moved(self.moved);
+        self.array.len = self.write + unvisited;
+        self.array.shrink_if_needed();
+    }
+}
+
+// Created by `RawArray::drain`. Yields the range by value; dropping it drops what was not
+// yielded and moves the tail back.
+pub struct Drain<'a, T, G: GrowthPolicy = Factor, A: RawAllocator = Global> {
+    array: &'a mut RawArray<T, G, A>,
+    next: usize,
+    end: usize,
+    tail_start: usize,
+    tail_len: usize,
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> Drain<'_, T, G, A> {
+    // The elements not yielded yet.
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { self.array.buf.slice(self.next, self.end) }
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> Iterator for Drain<'_, T, G, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next == self.end {
+            None
+        } else {
+            self.next += 1;
+            unsafe { Some(self.array.buf.read(self.next - 1)) }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> DoubleEndedIterator for Drain<'_, T, G, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.next == self.end {
+            None
+        } else {
+            self.end -= 1;
+            unsafe { Some(self.array.buf.read(self.end)) }
+        }
+    }
+}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> ExactSizeIterator for Drain<'_, T, G, A> {}
+
+impl<T, G: GrowthPolicy, A: RawAllocator> Drop for Drain<'_, T, G, A> {
+    fn drop(&mut self) {
+        // Moves the tail back even when dropping one of the remaining elements panics.
+        struct MoveTail<'r, 'a, T, G: GrowthPolicy, A: RawAllocator>(&'r mut Drain<'a, T, G, A>);
+
+        impl<T, G: GrowthPolicy, A: RawAllocator> Drop for MoveTail<'_, '_, T, G, A> {
+            fn drop(&mut self) {
+                let tail_len = self.0.tail_len;
+                let array = &mut *self.0.array;
+                let start = array.len;
+                if self.0.tail_start != start {
+                    unsafe { array.buf.shift(self.0.tail_start, start, tail_len) };
+                    array.moved(tail_len);
+                }
+                array.len = start + tail_len;
+                array.shrink_if_needed();
+            }
+        }
+
+        let (next, end) = (self.next, self.end);
+        self.next = end;
+        let guard = MoveTail(self);
+        unsafe { guard.0.array.buf.drop_range(next, end) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        allocator::Counting,
+        array::Array,
+        growth::{Exact, Fibonacci, Linear},
+        matrix_array::MatrixArray,
+        single_array::SingleArray,
+        sparse_array::SparseArray,
+        vector_array::VectorArray,
+    };
+    use std::{
+        cell::Cell,
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+        thread,
+    };
+
+    struct Fixed;
+
+    // Jumps straight to a large buffer and then behaves like `Exact`.
+    impl GrowthPolicy for Fixed {
+        fn grow(&self, cap: usize, required: usize) -> usize {
+            if cap == 0 {
+                64
+            } else {
+                required
+            }
+        }
+    }
+
+    #[test]
+    fn growth_policies() {
+        let mut array = RawArray::with_growth(Fixed);
+        (0..65).for_each(|x| array.push(x));
+        assert_eq!(65, array.capacity());
+
+        let mut array = RawArray::with_growth(Fibonacci);
+        (0..6).for_each(|x| array.push(x));
+        assert_eq!(8, array.capacity());
+
+        let mut array = RawArray::with_growth(Factor::new(1.5));
+        (0..4).for_each(|x| array.push(x));
+        assert_eq!(5, array.capacity());
+
+        let mut array: RawArray<i64, Linear> = (0..6).collect();
+        assert_eq!(6, array.capacity());
+        array.push(6);
+        assert_eq!(11, array.capacity());
+        assert_eq!(&[0, 1, 2, 3, 4, 5, 6], &array[..]);
+    }
+
+    #[test]
+    fn exact_auto_shrink_does_not_thrash() {
+        let counting = Counting::new(Global);
+        let mut array = RawArray::with_growth_in(Exact, &counting);
+        (0..100).for_each(|x| array.push(x));
+        array.set_auto_shrink(true);
+        let before = counting.reallocations();
+        for x in 0..1000 {
+            array.push(x);
+            array.pop();
+            array.pop();
+            array.push(x);
+        }
+        // Growing back to 101 once; every later push fits and no pop shrinks.
+        assert_eq!(1, counting.reallocations() - before);
+        while array.len() > 50 {
+            array.pop();
+        }
+        assert_eq!(76, array.capacity());
+        assert_eq!(2, counting.reallocations() - before);
+
+        // Bouncing between one and two elements, then between none and one.
+        while array.len() > 2 {
+            array.pop();
+        }
+        let calls = || counting.allocations() + counting.reallocations() + counting.deallocations();
+        for len in [1, 0] {
+            let before = calls();
+            for x in 0..100 {
+                array.pop();
+                array.push(x);
+                array.pop();
+                assert_eq!(len, array.len());
+                array.push(x);
+            }
+            array.pop();
+            assert!(calls() - before <= 2, "{} calls at {len}", calls() - before);
+        }
+        assert_eq!(1, array.capacity());
+    }
+
+    #[test]
+    fn bells_and_whistles() {
+        let mut bytes = Array::<u8>::new();
+        (0..=u8::MAX).for_each(|x| bytes.push(x));
+        assert_eq!(256, bytes.len());
+        assert_eq!(22, bytes[22]);
+        bytes[255] = 42;
+        assert_eq!(&42, bytes.last().unwrap());
+        *bytes.first_mut().unwrap() = 255;
+        assert_eq!(*bytes.first().unwrap(), bytes.get(254) + 1);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut units = Array::<()>::new();
+        assert_eq!(usize::MAX, units.capacity());
+        let array: &mut dyn IArray<()> = &mut units;
+        for i in 0..100 {
+            array.insert((), i / 2);
+        }
+        assert_eq!(100, array.size());
+        assert_eq!(&(), array.get(99));
+        array.remove(0);
+        array.remove(98);
+        assert_eq!(98, array.size());
+        assert_eq!(Some(()), units.pop());
+        assert_eq!(97, units.len());
+        assert_eq!(usize::MAX, units.capacity());
+    }
+
+    #[test]
+    fn zero_sized_drop() {
+        thread_local!(static DROPS: Cell<usize> = const { Cell::new(0) });
+
+        struct Marker;
+
+        impl Drop for Marker {
+            fn drop(&mut self) {
+                DROPS.with(|d| d.set(d.get() + 1));
+            }
+        }
+
+        let mut markers = Array::<Marker>::new();
+        let array: &mut dyn IArray<Marker> = &mut markers;
+        for _ in 0..10 {
+            array.push(Marker);
+        }
+        drop(array.remove(3));
+        assert_eq!(1, DROPS.with(Cell::get));
+        drop(markers);
+        assert_eq!(10, DROPS.with(Cell::get));
+    }
+
+    #[test]
+    fn fallible_allocation() {
+        let mut array = Array::<i64>::new();
+        assert_eq!(
+            Err(TryReserveError::CapacityOverflow),
+            array.try_reserve(usize::MAX)
+        );
+        assert_eq!(
+            Err(TryReserveError::CapacityOverflow),
+            array.try_reserve(isize::MAX as usize)
+        );
+        assert!(array.try_reserve(100).is_ok());
+        assert!(array.capacity() >= 100);
+        assert!(array.try_push(42).is_ok());
+        assert!(array.try_insert(-42, 0).is_ok());
+        assert_eq!(&[-42, 42], &array[..]);
+    }
+
+    #[test]
+    fn iteration() {
+        let mut array = Array::<i64>::new();
+        (0..5).for_each(|x| array.push(x));
+        IIterableMut::iter_mut(&mut array).for_each(|x| *x *= 2);
+        assert_eq!(20, IIterable::iter(&array).sum::<i64>());
+    }
+
+    #[test]
+    fn owning_iteration() {
+        let array: Array<String> = (0..10).map(|x| x.to_string()).collect();
+        assert_eq!(10, array.len());
+        let mut iter = array.into_iter();
+        assert_eq!(Some(String::from("0")), iter.next());
+        assert_eq!(Some(String::from("9")), iter.next_back());
+        assert_eq!(8, iter.len());
+        drop(iter);
+
+        let mut array = Array::<String>::new();
+        array.extend(["a", "b"].map(String::from));
+        array.extend(vec![String::from("c")]);
+        let joined: Vec<String> = array.into_iter().collect();
+        assert_eq!(vec!["a", "b", "c"], joined);
+    }
+
+    #[test]
+    fn capacity_management() {
+        let mut array = Array::<i64>::with_capacity(100);
+        assert_eq!(100, array.capacity());
+        (0..100).for_each(|x| array.push(x));
+        assert_eq!(100, array.capacity());
+        array.reserve_exact(10);
+        assert_eq!(110, array.capacity());
+        array.reserve(5);
+        assert_eq!(110, array.capacity());
+        (20..100).for_each(|_| {
+            array.pop();
+        });
+        array.shrink_to(50);
+        assert_eq!(50, array.capacity());
+        array.shrink_to(0);
+        assert_eq!(20, array.capacity());
+        while array.pop().is_some() {}
+        array.shrink_to_fit();
+        assert_eq!(0, array.capacity());
+        array.push(7);
+        assert_eq!(&[7], &array[..]);
+    }
+
+    #[test]
+    fn auto_shrink() {
+        let mut array = Array::<i64>::new();
+        (0..1024).for_each(|x| array.push(x));
+        while array.len() > 300 {
+            array.pop();
+        }
+        assert_eq!(1024, array.capacity());
+
+        array.set_auto_shrink(true);
+        while array.len() > 255 {
+            array.pop();
+        }
+        assert_eq!(512, array.capacity());
+        // Right at the threshold push/pop must not reallocate back and forth.
+        for _ in 0..100 {
+            array.push(0);
+            array.pop();
+            array.remove(0);
+            array.insert(0, 0);
+        }
+        assert_eq!(512, array.capacity());
+        while array.pop().is_some() {}
+        assert_eq!(2, array.capacity());
+        assert_eq!(None, array.pop());
+
+        let mut array = VectorArray::<i64>::new(10);
+        (0..100).for_each(|x| array.push(x));
+        array.set_auto_shrink(true);
+        while array.len() > 81 {
+            array.pop();
+        }
+        assert_eq!(100, array.capacity());
+        array.remove(0);
+        assert_eq!(90, array.capacity());
+        // One step of slack is kept, so push/pop at the edge does not reallocate.
+        for _ in 0..100 {
+            array.push(0);
+            array.pop();
+        }
+        assert_eq!(90, array.capacity());
+
+        let mut array = SingleArray::<i64>::new();
+        (0..10).for_each(|x| array.push(x));
+        array.set_auto_shrink(true);
+        array.remove(0);
+        array.pop();
+        assert_eq!(10, array.capacity());
+        (6..9).rev().for_each(|x| assert_eq!(Some(x), array.pop()));
+        assert_eq!(8, array.capacity());
+        while array.pop().is_some() {}
+        // One slot is kept, so pushing again does not allocate.
+        assert_eq!(1, array.capacity());
+    }
+
+    #[test]
+    fn bulk_removal() {
+        let mut array: Array<i32> = (0..10).collect();
+        array.retain(|x| x % 3 != 0);
+        assert_eq!(&[1, 2, 4, 5, 7, 8], &array[..]);
+        array.retain_mut(|x| {
+            *x *= 10;
+            *x < 60
+        });
+        assert_eq!(&[10, 20, 40, 50], &array[..]);
+        array.truncate(10);
+        array.truncate(3);
+        assert_eq!(&[10, 20, 40], &array[..]);
+        array.clear();
+        assert!(array.is_empty());
+
+        let mut array: SingleArray<i32> = [1, 1, 2, 3, 3, 3, 1, 4].into_iter().collect();
+        array.dedup();
+        assert_eq!(&[1, 2, 3, 1, 4], &array[..]);
+        array.dedup_by_key(|x| *x / 2);
+        assert_eq!(&[1, 2, 1, 4], &array[..]);
+
+        let mut array: VectorArray<i32> = (0..10).collect();
+        assert_eq!(vec![2, 3, 4], array.drain(2..5).collect::<Vec<_>>());
+        assert_eq!(&[0, 1, 5, 6, 7, 8, 9], &array[..]);
+        let mut drain = array.drain(1..=4);
+        assert_eq!(Some(1), drain.next());
+        assert_eq!(Some(7), drain.next_back());
+        assert_eq!(&[5, 6], drain.as_slice());
+        drop(drain);
+        assert_eq!(&[0, 8, 9], &array[..]);
+        assert_eq!(3, array.drain(..).len());
+        assert!(array.is_empty());
+    }
+
+    #[test]
+    fn bulk_removal_drops_once() {
+        let counter = Rc::new(());
+        let mut array: Array<Rc<()>> = (0..10).map(|_| Rc::clone(&counter)).collect();
+        let mut seen = 0;
+        array.retain(|_| {
+            seen += 1;
+            seen % 2 == 0
+        });
+        assert_eq!(6, Rc::strong_count(&counter));
+        array.drain(1..3).next();
+        assert_eq!(4, Rc::strong_count(&counter));
+        array.push(Rc::clone(&counter));
+        array.dedup_by(|a, b| Rc::ptr_eq(a, b));
+        assert_eq!(2, Rc::strong_count(&counter));
+        std::mem::forget(array.drain(..));
+        assert_eq!(0, array.len());
+        assert_eq!(2, Rc::strong_count(&counter));
+
+        let mut units: Array<()> = (0..5).map(|_| ()).collect();
+        units.drain(1..3);
+        units.retain(|_| true);
+        assert_eq!(3, units.len());
+    }
+
+    #[test]
+    fn bulk_insertion() {
+        let mut array: Array<i64> = (0..6).collect();
+        array.insert_many(2, [10, 11, 12]);
+        assert_eq!(&[0, 1, 10, 11, 12, 2, 3, 4, 5], &array[..]);
+        // A filter has no lower bound, so the tail has to move a second time.
+        array.insert_many(9, (20..30).filter(|x| x % 2 == 0));
+        assert_eq!(&[20, 22, 24, 26, 28], &array[9..]);
+        let removed = array.splice(1..5, [-1]);
+        assert_eq!(vec![1, 10, 11, 12], removed.collect::<Vec<_>>());
+        assert_eq!(&[0, -1, 2, 3, 4, 5, 20, 22, 24, 26, 28], &array[..]);
+        array.splice(..2, std::iter::empty());
+        assert_eq!(&[2, 3, 4, 5, 20, 22, 24, 26, 28], &array[..]);
+
+        let mut tail = array.split_off(4);
+        assert_eq!(&[2, 3, 4, 5], &array[..]);
+        assert_eq!(&[20, 22, 24, 26, 28], &tail[..]);
+        array.append(&mut tail);
+        assert!(tail.is_empty());
+        assert_eq!(9, array.len());
+        let mut other: VectorArray<i64> = (100..103).collect();
+        array.append(&mut other);
+        assert_eq!(&[28, 100, 101, 102], &array[8..]);
+        array.extend_from_slice(&[7, 8]);
+        assert_eq!(&[102, 7, 8], &array[11..]);
+        array.extend_from_copy_slice(&[9, 10, 11]);
+        array.extend_from_copy_slice(&[]);
+        assert_eq!(&[8, 9, 10, 11], &array[13..]);
+        let mut units = VectorArray::new(2);
+        units.extend_from_copy_slice(&[(); 5]);
+        assert_eq!(5, units.len());
+
+        let mut strings = SingleArray::new();
+        strings.extend_from_slice(&[String::from("a"), String::from("b")]);
+        strings.insert_many(1, ["x", "y"].map(String::from));
+        assert_eq!(&["a", "x", "y", "b"], &strings[..]);
+        let rest = strings.split_off(0);
+        assert!(strings.is_empty());
+        assert_eq!(4, rest.len());
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Fuse {
+        Safe,
+        OnDrop,
+        OnClone,
+    }
+
+    // Counts how often it is dropped and panics in `drop` or `clone` when told to.
+    #[derive(Debug)]
+    struct Bomb<'a> {
+        drops: &'a Cell<usize>,
+        fuse: Fuse,
+    }
+
+    impl Clone for Bomb<'_> {
+        fn clone(&self) -> Self {
+            assert_ne!(Fuse::OnClone, self.fuse, "clone exploded");
+            Bomb {
+                drops: self.drops,
+                fuse: self.fuse,
+            }
+        }
+    }
+
+    impl Drop for Bomb<'_> {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+            if self.fuse == Fuse::OnDrop && !thread::panicking() {
+                panic!("drop exploded");
+            }
+        }
+    }
+
+    // Ten bombs, the fourth with the given fuse, in an array whose allocations are counted.
+    fn bombs<'a>(
+        drops: &'a Cell<usize>,
+        counting: &'a Counting,
+        fuse: Fuse,
+    ) -> RawArray<Bomb<'a>, Factor, &'a Counting> {
+        let mut array = RawArray::with_growth_in(Factor::default(), counting);
+        for i in 0..10 {
+            let fuse = if i == 3 { fuse } else { Fuse::Safe };
+            array.push(Bomb { drops, fuse });
+        }
+        array
+    }
+
+    fn assert_panics(f: impl FnOnce()) {
+        assert!(panic::catch_unwind(AssertUnwindSafe(f)).is_err());
+    }
+
+    #[test]
+    fn panicking_drop() {
+        let (drops, counting) = (Cell::new(0), Counting::new(Global));
+        assert_panics(|| drop(bombs(&drops, &counting, Fuse::OnDrop)));
+        assert_eq!(10, drops.get());
+        assert_eq!(0, counting.bytes_in_use());
+
+        drops.set(0);
+        assert_panics(|| {
+            let mut iter = bombs(&drops, &counting, Fuse::OnDrop).into_iter();
+            iter.next();
+            drop(iter);
+        });
+        assert_eq!(10, drops.get());
+        assert_eq!(0, counting.bytes_in_use());
+
+        drops.set(0);
+        let mut array = bombs(&drops, &counting, Fuse::OnDrop);
+        assert_panics(|| array.truncate(2));
+        assert_eq!(2, array.len());
+        assert_eq!(8, drops.get());
+        drop(array);
+        assert_eq!(10, drops.get());
+
+        drops.set(0);
+        let mut array = bombs(&drops, &counting, Fuse::OnDrop);
+        assert_panics(|| drop(array.drain(2..6)));
+        assert_eq!(6, array.len());
+        assert_eq!(4, drops.get());
+        drop(array);
+        assert_eq!(10, drops.get());
+
+        drops.set(0);
+        let mut array = bombs(&drops, &counting, Fuse::OnDrop);
+        assert_panics(|| array.retain(|bomb| bomb.fuse == Fuse::Safe));
+        assert_eq!(9, array.len());
+        drop(array);
+        assert_eq!(10, drops.get());
+        assert_eq!(0, counting.bytes_in_use());
+    }
+
+    #[test]
+    fn panicking_clone() {
+        let (drops, counting) = (Cell::new(0), Counting::new(Global));
+        let array = bombs(&drops, &counting, Fuse::OnClone);
+        assert_panics(|| drop(array.clone()));
+        assert_eq!(3, drops.get());
+        let mut other = RawArray::with_growth_in(Factor::default(), &counting);
+        assert_panics(|| other.extend_from_slice(&array));
+        assert_eq!(3, other.len());
+        assert_panics(|| drop(MatrixArray::from_iter(array.iter().cloned())));
+        drop((array, other));
+        assert_eq!(3 + 13 + 3, drops.get());
+        assert_eq!(0, counting.bytes_in_use());
+    }
+
+    #[test]
+    fn panicking_callbacks() {
+        let mut array: Array<i32> = (0..10).collect();
+        assert_panics(|| {
+            array.retain(|x| {
+                assert!(*x != 5);
+                x % 2 == 0
+            })
+        });
+        assert_eq!(&[0, 2, 4, 5, 6, 7, 8, 9], &array[..]);
+
+        let mut array: Array<i32> = (0..10).collect();
+        assert_panics(|| {
+            array.insert_many(2, (0..5).map(|x| if x < 3 { x * 100 } else { panic!() }));
+        });
+        assert_eq!(&[0, 1, 0, 100, 200, 2, 3], &array[..7]);
+        assert_eq!(13, array.len());
+
+        let mut array: Array<i32> = (0..10).collect();
+        let mut drained = 0;
+        assert_panics(|| {
+            for _ in array.drain(2..8) {
+                drained += 1;
+                assert!(drained < 3);
+            }
+        });
+        assert_eq!(&[0, 1, 8, 9], &array[..]);
+
+        let mut sparse: SparseArray<u8> = [0, 1, 0, 2, 3].into_iter().collect();
+        assert_panics(|| sparse.retain(|x| *x != 2 || panic!()));
+        assert_eq!("[0, 1, 0]", format!("{}", sparse));
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    alloc::Layout,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
+
+use crate::{
+    allocator::{Global, RawAllocator},
+    error::TryReserveError,
+};
+
+// The one place where the arrays allocate, free and touch raw slots.
+//
+// A `RawBuf` owns `cap` slots but does not know which of them hold a value: that is the
+// caller's bookkeeping, and every unsafe method below says which slots must be initialized
+// or uninitialized. Dropping it frees the memory and never drops elements. All pointers
+// are derived from `ptr` itself, never from a reference to the contents, so a pointer
+// handed out by `as_ptr` stays valid across later calls that do not reallocate.
+pub(crate) struct RawBuf<T, A: RawAllocator = Global> {
+    ptr: NonNull<T>,
+    cap: usize,
+    alloc: A,
+    _marker: PhantomData<T>,
+}
+
+// The buffer is uniquely owned, like the one in a `Vec<T>`.
+unsafe impl<T: Send, A: RawAllocator + Send> Send for RawBuf<T, A> {}
+unsafe impl<T: Sync, A: RawAllocator + Sync> Sync for RawBuf<T, A> {}
+
+impl<T, A: RawAllocator> RawBuf<T, A> {
+    pub(crate) fn new_in(alloc: A) -> Self {
+        // Zero-sized types never need an allocation, so the capacity is unlimited.
+        let cap = if mem::size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            0
+        };
+        Self {
+            ptr: NonNull::dangling(),
+            cap,
+            alloc,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub(crate) fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    // Whether there is memory behind the buffer, i.e. whether dropping it deallocates.
+    pub(crate) fn is_allocated(&self) -> bool {
+        self.cap != 0 && mem::size_of::<T>() != 0
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    // Moves the slots to a buffer of `new_cap` slots, `0` frees it. The first
+    // `min(cap, new_cap)` slots keep their contents; the caller must have dropped or moved
+    // out of the ones beyond `new_cap`. On failure the buffer is left untouched.
+    pub(crate) fn try_set_capacity(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        if mem::size_of::<T>() == 0 || new_cap == self.cap {
+            return Ok(());
+        }
+        if new_cap == 0 {
+            self.free();
+            self.ptr = NonNull::dangling();
+            self.cap = 0;
+            return Ok(());
+        }
+        // `Layout::array` fails if the allocation would exceed `isize::MAX` bytes.
+        let new_layout =
+            Layout::array::<T>(new_cap).map_err(|_| TryReserveError::CapacityOverflow)?;
+        let new_ptr = if self.cap == 0 {
+            self.alloc.allocate(new_layout)
+        } else {
+            // SAFETY: `ptr` was allocated by `alloc` with exactly this layout.
+            unsafe {
+                self.alloc
+                    .reallocate(self.ptr.cast::<u8>(), self.layout(), new_layout.size())
+            }
+        };
+        self.ptr = new_ptr
+            .ok_or(TryReserveError::AllocError { layout: new_layout })?
+            .cast::<T>();
+        self.cap = new_cap;
+        Ok(())
+    }
+
+    // SAFETY: `index` must be an initialized slot, which is uninitialized afterwards.
+    pub(crate) unsafe fn read(&self, index: usize) -> T {
+        debug_assert!(index < self.cap);
+        ptr::read(self.as_ptr().add(index))
+    }
+
+    // SAFETY: `index` must be an uninitialized slot, which is initialized afterwards.
+    pub(crate) unsafe fn write(&mut self, index: usize, elem: T) {
+        debug_assert!(index < self.cap);
+        ptr::write(self.as_ptr().add(index), elem);
+    }
+
+    // Moves `count` slots from `src` to `dst`; the ranges may overlap.
+    // SAFETY: both ranges must be in bounds. The source slots count as uninitialized
+    // afterwards, except where they overlap the destination.
+    pub(crate) unsafe fn shift(&mut self, src: usize, dst: usize, count: usize) {
+        debug_assert!(src
+            .max(dst)
+            .checked_add(count)
+            .is_some_and(|end| end <= self.cap));
+        if count != 0 && src != dst {
+            ptr::copy(self.as_ptr().add(src), self.as_ptr().add(dst), count);
+        }
+    }
+
+    // Moves `count` slots starting at `src` into `other` starting at `dst`.
+    // SAFETY: the source slots must be initialized and the destination ones uninitialized;
+    // afterwards it is the other way round.
+    pub(crate) unsafe fn move_to<B: RawAllocator>(
+        &mut self,
+        src: usize,
+        other: &mut RawBuf<T, B>,
+        dst: usize,
+        count: usize,
+    ) {
+        debug_assert!(src + count <= self.cap && dst + count <= other.cap);
+        ptr::copy_nonoverlapping(self.as_ptr().add(src), other.as_ptr().add(dst), count);
+    }
+
+    // Copies `src` into the slots starting at `dst`.
+    // SAFETY: the destination slots must be in bounds and uninitialized; they are initialized
+    // afterwards.
+    pub(crate) unsafe fn copy_from_slice(&mut self, dst: usize, src: &[T])
+    where
+        T: Copy,
+    {
+        debug_assert!(dst + src.len() <= self.cap);
+        ptr::copy_nonoverlapping(src.as_ptr(), self.as_ptr().add(dst), src.len());
+    }
+
+    // Drops the elements in `start..end`, all of them even if one of the drops panics.
+    // SAFETY: the slots must be initialized; they are uninitialized afterwards.
+    pub(crate) unsafe fn drop_range(&mut self, start: usize, end: usize) {
+        debug_assert!(start <= end && end <= self.cap);
+        let elems = ptr::slice_from_raw_parts_mut(self.as_ptr().add(start), end - start);
+        ptr::drop_in_place(elems);
+    }
+
+    // SAFETY: the slots in `start..end` must be initialized.
+    pub(crate) unsafe fn slice(&self, start: usize, end: usize) -> &[T] {
+        debug_assert!(start <= end && end <= self.cap);
+        slice::from_raw_parts(self.as_ptr().add(start), end - start)
+    }
+
+    // SAFETY: the slots in `start..end` must be initialized.
+    pub(crate) unsafe fn slice_mut(&mut self, start: usize, end: usize) -> &mut [T] {
+        debug_assert!(start <= end && end <= self.cap);
+        slice::from_raw_parts_mut(self.as_ptr().add(start), end - start)
+    }
+
+    // SAFETY: the slots in both ranges must be initialized and the ranges must not overlap.
+    pub(crate) unsafe fn slice_pair_mut(
+        &mut self,
+        (a_start, a_end): (usize, usize),
+        (b_start, b_end): (usize, usize),
+    ) -> (&mut [T], &mut [T]) {
+        debug_assert!(a_end <= b_start || b_end <= a_start);
+        debug_assert!(a_start <= a_end && a_end <= self.cap);
+        debug_assert!(b_start <= b_end && b_end <= self.cap);
+        (
+            slice::from_raw_parts_mut(self.as_ptr().add(a_start), a_end - a_start),
+            slice::from_raw_parts_mut(self.as_ptr().add(b_start), b_end - b_start),
+        )
+    }
+
+    // SAFETY: `index` must be an initialized slot.
+    pub(crate) unsafe fn get(&self, index: usize) -> &T {
+        debug_assert!(index < self.cap);
+        &*self.as_ptr().add(index)
+    }
+
+    // SAFETY: `index` must be an initialized slot.
+    pub(crate) unsafe fn get_mut(&mut self, index: usize) -> &mut T {
+        debug_assert!(index < self.cap);
+        &mut *self.as_ptr().add(index)
+    }
+
+    // SAFETY: both slots must be initialized and `a != b`.
+    pub(crate) unsafe fn get_pair_mut(&mut self, a: usize, b: usize) -> (&mut T, &mut T) {
+        debug_assert!(a != b && a.max(b) < self.cap);
+        (&mut *self.as_ptr().add(a), &mut *self.as_ptr().add(b))
+    }
+
+    fn layout(&self) -> Layout {
+        // The same computation succeeded when the buffer was allocated.
+        Layout::array::<T>(self.cap).unwrap()
+    }
+
+    fn free(&mut self) {
+        if self.is_allocated() {
+            // SAFETY: `ptr` was allocated by `alloc` with exactly this layout.
+            unsafe { self.alloc.deallocate(self.ptr.cast::<u8>(), self.layout()) };
+        }
+    }
+}
+
+impl<T, A: RawAllocator> Drop for RawBuf<T, A> {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::Counting;
+
+    #[test]
+    fn slots() {
+        let counting = Counting::new(Global);
+        let mut buf = RawBuf::new_in(&counting);
+        buf.try_set_capacity(4).unwrap();
+        unsafe {
+            (0..3).for_each(|i| buf.write(i, i.to_string()));
+            buf.shift(0, 1, 3);
+            buf.write(0, String::from("x"));
+            assert_eq!(&["x", "0", "1", "2"], buf.slice(0, 4));
+            let (a, b) = buf.get_pair_mut(0, 3);
+            std::mem::swap(a, b);
+            assert_eq!("x", buf.read(3));
+            buf.drop_range(0, 3);
+        }
+        buf.try_set_capacity(0).unwrap();
+        assert!(!buf.is_allocated());
+        assert_eq!(0, counting.bytes_in_use());
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut buf = RawBuf::<(), Global>::new_in(Global);
+        assert_eq!(usize::MAX, buf.capacity());
+        buf.try_set_capacity(10).unwrap();
+        assert_eq!(usize::MAX, buf.capacity());
+        assert!(!buf.is_allocated());
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::{error::TryReserveError, raw_buf::RawBuf};
+
+#[cfg(feature = "stats")]
+use {
+    crate::stats::{self, Event},
+    std::mem,
+};
+
+// A fixed-capacity ring buffer, the block type of `MatrixArray`. Elements can be added and
+// taken at both ends in O(1), and inserting or removing in the middle moves only the
+// shorter side. The `len` slots starting at `head` and wrapping around at `cap` are
+// initialized, the rest are not; all unsafe code relies on that.
+pub(crate) struct Ring<T> {
+    buf: RawBuf<T>,
+    head: usize,
+    len: usize,
+    cap: usize,
+}
+
+impl<T> Ring<T> {
+    pub(crate) fn with_capacity(cap: usize) -> Result<Self, TryReserveError> {
+        let mut buf = RawBuf::new_in(Default::default());
+        buf.try_set_capacity(cap)?;
+        #[cfg(feature = "stats")]
+        if buf.is_allocated() {
+            stats::record_global(Event::Allocated { cap });
+        }
+        Ok(Self {
+            buf,
+            head: 0,
+            len: 0,
+            cap,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.len == self.cap
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        Some(unsafe { self.buf.get(self.slot(index)) })
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        let slot = self.slot(index);
+        Some(unsafe { self.buf.get_mut(slot) })
+    }
+
+    pub(crate) fn push_front(&mut self, elem: T) {
+        assert!(!self.is_full(), "ring is full");
+        self.head = self.slot(self.cap - 1);
+        unsafe { self.buf.write(self.head, elem) };
+        self.len += 1;
+    }
+
+    pub(crate) fn push_back(&mut self, elem: T) {
+        assert!(!self.is_full(), "ring is full");
+        let slot = self.slot(self.len);
+        unsafe { self.buf.write(slot, elem) };
+        self.len += 1;
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let elem = unsafe { self.buf.read(self.head) };
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(elem)
+    }
+
+    pub(crate) fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.buf.read(self.slot(self.len)) })
+    }
+
+    pub(crate) fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len, "index out of bounds");
+        assert!(!self.is_full(), "ring is full");
+        if index < self.len - index {
+            self.head = self.slot(self.cap - 1);
+            (0..index).for_each(|i| self.move_elem(i + 1, i));
+        } else {
+            (index..self.len)
+                .rev()
+                .for_each(|i| self.move_elem(i, i + 1));
+        }
+        let slot = self.slot(index);
+        unsafe { self.buf.write(slot, elem) };
+        self.len += 1;
+    }
+
+    pub(crate) fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "index out of bounds");
+        let elem = unsafe { self.buf.read(self.slot(index)) };
+        if index < self.len - 1 - index {
+            (0..index).rev().for_each(|i| self.move_elem(i, i + 1));
+            self.head = self.slot(1);
+        } else {
+            (index + 1..self.len).for_each(|i| self.move_elem(i, i - 1));
+        }
+        self.len -= 1;
+        elem
+    }
+
+    // The elements in order: the part up to the end of the buffer, then the wrapped part.
+    pub(crate) fn as_slices(&self) -> (&[T], &[T]) {
+        let (first, second) = self.ranges();
+        unsafe {
+            (
+                self.buf.slice(first.0, first.1),
+                self.buf.slice(second.0, second.1),
+            )
+        }
+    }
+
+    pub(crate) fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (first, second) = self.ranges();
+        unsafe { self.buf.slice_pair_mut(first, second) }
+    }
+
+    // Rotates the elements so that they are one slice; a no-op unless the ring wraps around.
+    pub(crate) fn make_contiguous(&mut self) -> &mut [T] {
+        let ((head, end), (_, wrapped)) = self.ranges();
+        if wrapped > 0 {
+            // Closes the gap in front of the head with the wrapped part, so that the elements
+            // fill the end of the buffer, the wrapped part first, and then rotates them.
+            let free = self.cap - self.len;
+            unsafe {
+                self.buf.shift(0, free, wrapped);
+                self.buf.slice_mut(free, self.cap).rotate_left(wrapped);
+            }
+            #[cfg(feature = "stats")]
+            stats::record_global(Event::Moved {
+                bytes: (wrapped + self.len) * mem::size_of::<T>(),
+            });
+            self.head = free;
+            return unsafe { self.buf.slice_mut(free, self.cap) };
+        }
+        unsafe { self.buf.slice_mut(head, end) }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        let (first, second) = self.as_slices();
+        first.iter().chain(second)
+    }
+
+    // The physical slot of the element at `index`, for `index <= cap`.
+    fn slot(&self, index: usize) -> usize {
+        let slot = self.head + index;
+        if slot >= self.cap {
+            slot - self.cap
+        } else {
+            slot
+        }
+    }
+
+    fn ranges(&self) -> ((usize, usize), (usize, usize)) {
+        if self.head + self.len <= self.cap {
+            ((self.head, self.head + self.len), (0, 0))
+        } else {
+            ((self.head, self.cap), (0, self.head + self.len - self.cap))
+        }
+    }
+
+    // Moves the element at logical index `from` into the free slot at logical index `to`.
+    fn move_elem(&mut self, from: usize, to: usize) {
+        let (from, to) = (self.slot(from), self.slot(to));
+        unsafe { self.buf.shift(from, to, 1) };
+        #[cfg(feature = "stats")]
+        stats::record_global(Event::Moved {
+            bytes: mem::size_of::<T>(),
+        });
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        // Drops the wrapped part even if dropping an element of the first part panics.
+        struct Wrapped<'a, T>(&'a mut RawBuf<T>, usize);
+
+        impl<T> Drop for Wrapped<'_, T> {
+            fn drop(&mut self) {
+                unsafe { self.0.drop_range(0, self.1) };
+            }
+        }
+
+        #[cfg(feature = "stats")]
+        if self.buf.is_allocated() {
+            stats::record_global(Event::Deallocated);
+        }
+        let (first, second) = self.ranges();
+        let wrapped = Wrapped(&mut self.buf, second.1);
+        unsafe { wrapped.0.drop_range(first.0, first.1) };
+        // The buffer is freed by its own `Drop`.
+        drop(wrapped);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Ring<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> IntoIterator for Ring<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { ring: self }
+    }
+}
+
+// Unyielded elements are dropped together with the ring.
+pub(crate) struct IntoIter<T> {
+    ring: Ring<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.ring.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.ring.len(), Some(self.ring.len()))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.ring.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn contents(ring: &Ring<i32>) -> Vec<i32> {
+        ring.iter().copied().collect()
+    }
+
+    #[test]
+    fn both_ends() {
+        let mut ring = Ring::with_capacity(4).unwrap();
+        ring.push_back(1);
+        ring.push_front(0);
+        ring.push_front(-1);
+        ring.push_back(2);
+        assert!(ring.is_full());
+        assert_eq!(vec![-1, 0, 1, 2], contents(&ring));
+        assert_eq!((&[-1, 0][..], &[1, 2][..]), ring.as_slices());
+        assert_eq!(Some(-1), ring.pop_front());
+        assert_eq!(Some(2), ring.pop_back());
+        ring.push_back(3);
+        ring.push_back(4);
+        assert_eq!(vec![0, 1, 3, 4], contents(&ring));
+        assert_eq!(Some(&3), ring.get(2));
+        assert_eq!(None, ring.get(4));
+        *ring.get_mut(0).unwrap() = 10;
+        ring.as_mut_slices().1[0] = 30;
+        assert_eq!(vec![10, 30, 3, 4], ring.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn insert_and_remove_wrapped() {
+        for head in 0..7 {
+            let mut ring = Ring::with_capacity(7).unwrap();
+            (0..head).for_each(|_| ring.push_back(0));
+            (0..head).for_each(|_| assert_eq!(Some(0), ring.pop_front()));
+            let mut oracle = Vec::new();
+            for (i, index) in [0, 1, 1, 3, 2, 5].into_iter().enumerate() {
+                ring.insert(index, i as i32);
+                oracle.insert(index, i as i32);
+                assert_eq!(oracle, contents(&ring));
+            }
+            for index in [5, 0, 2, 1, 1] {
+                assert_eq!(oracle.remove(index), ring.remove(index));
+                assert_eq!(oracle, contents(&ring));
+            }
+        }
+    }
+
+    #[test]
+    fn make_contiguous() {
+        // (capacity, elements pushed at the front, elements pushed at the back)
+        let cases = [
+            (5, 2, 3),
+            (5, 1, 2),
+            (7, 3, 1),
+            (7, 1, 5),
+            (4, 0, 4),
+            (4, 3, 1),
+        ];
+        for (cap, fronts, backs) in cases {
+            let mut ring = Ring::with_capacity(cap).unwrap();
+            (0..backs).for_each(|x| ring.push_back(x));
+            (1..=fronts).for_each(|x| ring.push_front(-x));
+            let expected = contents(&ring);
+            assert_eq!(&expected[..], ring.make_contiguous());
+            assert_eq!((&expected[..], &[][..]), ring.as_slices());
+            if !ring.is_full() {
+                ring.push_back(9);
+                assert_eq!(Some(9), ring.pop_back());
+            }
+            assert_eq!(expected, contents(&ring));
+        }
+    }
+
+    #[test]
+    fn drops_every_element_once() {
+        let rc = Rc::new(());
+        let mut ring = Ring::with_capacity(5).unwrap();
+        (0..3).for_each(|_| ring.push_front(Rc::clone(&rc)));
+        (0..2).for_each(|_| ring.push_back(Rc::clone(&rc)));
+        drop(ring.remove(2));
+        let mut iter = ring.into_iter();
+        iter.next_back();
+        assert_eq!(5 - 2 + 1, Rc::strong_count(&rc));
+        drop(iter);
+        assert_eq!(1, Rc::strong_count(&rc));
+
+        let empty = Ring::<Rc<()>>::with_capacity(0).unwrap();
+        assert!(empty.is_full() && empty.is_empty());
+    }
+}
@@ -1,10 +1,30 @@
-use crate::{array::Array, IArray};
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::Peekable,
+    mem,
+    ops::RangeBounds,
+    slice, vec,
+};
 
-#[derive(Default)]
+use crate::{
+    allocator::RawAllocator,
+    array::Array,
+    error::{IndexError, TryReserveError},
+    matrix_array::MatrixArray,
+    range_bounds,
+    raw_array::{self, RawArray},
+    IArray, IIterable,
+};
+
+#[derive(Default, Clone)]
 pub struct SparseArray<T: Default> {
     inner: Array<Value<T>>,
     zero: T,
     len: usize,
+    // Set by `get_mut`, which may leave a zero stored; the next mutation drops it again.
+    dirty: bool,
 }
 
 impl<T: Default + PartialEq> SparseArray<T> {
@@ -15,6 +35,13 @@ impl<T: Default + PartialEq> SparseArray<T> {
         }
     }
 
+    fn compact(&mut self) {
+        if mem::take(&mut self.dirty) {
+            let zero = &self.zero;
+            self.inner.retain(|current| current.value != *zero);
+        }
+    }
+
     fn find_inner_value(&self, index: usize) -> Option<&Value<T>> {
         self.inner.iter().find(|current| current.index == index)
     }
@@ -52,13 +79,13 @@ impl<T: Default + PartialEq> SparseArray<T> {
             v.index += by;
         }
     }
-    
+
     pub fn repr(&self) -> String
     where
         T: std::fmt::Display,
     {
         let mut result = vec![];
-        for v in self.inner.iter() {
+        for v in self.inner.iter().filter(|v| v.value != self.zero) {
             result.push(format!("({}, {})", v.index, v.value));
         }
         format!("[{}]", result.join(", "))
@@ -67,10 +94,7 @@ impl<T: Default + PartialEq> SparseArray<T> {
 
 impl<T: std::fmt::Display + Default + PartialEq> std::fmt::Display for SparseArray<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut result = vec![];
-        for i in 0..self.len {
-            result.push(format!("{}", self.get(i)));
-        }
+        let result: Vec<String> = self.iter().map(|v| format!("{}", v)).collect();
         write!(f, "[{}]", result.join(", "))
     }
 }
@@ -91,7 +115,46 @@ impl<T: Default + PartialEq> IArray<T> for SparseArray<T> {
         }
     }
 
+    // Writing through a reference to an implicit zero has to store it first. If it is still
+    // zero afterwards, the next mutation removes it, so at most one zero is stored at a time.
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        self.compact();
+        self.dirty = true;
+        let pos = match self.find_inner_pos(index) {
+            Some(pos) => pos,
+            None => {
+                let pos = self.find_pos_to_insert_value(index);
+                self.inner
+                    .insert(Value::new(index, Default::default()), pos);
+                pos
+            }
+        };
+        Some(&mut self.inner[pos].value)
+    }
+
+    fn set(&mut self, index: usize, elem: T) -> T {
+        if index >= self.len {
+            panic!("{}", IndexError::new(index, self.len));
+        }
+        self.compact();
+        match self.find_inner_pos(index) {
+            Some(pos) if self.zero == elem => self.inner.remove(pos).value,
+            Some(pos) => mem::replace(&mut self.inner[pos].value, elem),
+            None => {
+                if self.zero != elem {
+                    let pos = self.find_pos_to_insert_value(index);
+                    self.inner.insert(Value::new(index, elem), pos);
+                }
+                Default::default()
+            }
+        }
+    }
+
     fn insert(&mut self, elem: T, index: usize) {
+        self.compact();
         if index < self.len {
             self.inner_insert(elem, index);
         } else {
@@ -103,7 +166,10 @@ impl<T: Default + PartialEq> IArray<T> for SparseArray<T> {
     }
 
     fn remove(&mut self, index: usize) -> T {
-        assert!(index < self.len);
+        if index >= self.len {
+            panic!("{}", IndexError::new(index, self.len));
+        }
+        self.compact();
         self.len -= 1;
         match self.find_inner_pos(index) {
             Some(pos) => {
@@ -117,9 +183,235 @@ impl<T: Default + PartialEq> IArray<T> for SparseArray<T> {
             }
         }
     }
+
+    // Reserves for the worst case, where none of the new elements is zero.
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+
+    fn try_insert(&mut self, elem: T, index: usize) -> Result<(), TryReserveError> {
+        if self.zero != elem {
+            self.inner.try_reserve(1)?;
+        }
+        self.insert(elem, index);
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.compact();
+        if len < self.len {
+            let pos = self.find_pos_to_insert_value(len);
+            self.inner.truncate(pos);
+            self.len = len;
+        }
+    }
+
+    // Walks every index, implicit zeros included, and pushes the survivors back.
+    fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut keep: F) {
+        let len = mem::take(&mut self.len);
+        self.dirty = false;
+        let mut stored = mem::take(&mut self.inner).into_iter().peekable();
+        for index in 0..len {
+            let mut elem = match stored.next_if(|current| current.index == index) {
+                Some(current) => current.value,
+                None => Default::default(),
+            };
+            if keep(&mut elem) {
+                self.push(elem);
+            }
+        }
+    }
+
+    fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> vec::IntoIter<T> {
+        let (start, end) = range_bounds(range, self.len);
+        let mut drained = Vec::with_capacity(end - start);
+        let mut index = 0;
+        self.retain_mut(|elem| {
+            let keep = !(start..end).contains(&index);
+            if !keep {
+                drained.push(mem::take(elem));
+            }
+            index += 1;
+            keep
+        });
+        drained.into_iter()
+    }
+}
+
+impl<T: Default + PartialEq> IIterable<T> for SparseArray<T> {
+    type Iter<'a>
+        = Iter<'a, T>
+    where
+        T: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        Iter {
+            stored: self.inner.iter().peekable(),
+            zero: &self.zero,
+            index: 0,
+            len: self.len,
+        }
+    }
+}
+
+// Stored values are sorted by index, so merging them with the gaps takes one pass.
+pub struct Iter<'a, T> {
+    stored: Peekable<slice::Iter<'a, Value<T>>>,
+    zero: &'a T,
+    index: usize,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.len {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        match self.stored.next_if(|v| v.index == index) {
+            Some(v) => Some(&v.value),
+            None => Some(self.zero),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T: fmt::Debug + Default + PartialEq> fmt::Debug for SparseArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// Compares the logical sequences, implicit zeros included.
+impl<T, U> PartialEq<SparseArray<U>> for SparseArray<T>
+where
+    T: Default + PartialEq + PartialEq<U>,
+    U: Default + PartialEq,
+{
+    fn eq(&self, other: &SparseArray<U>) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T, U, G, A> PartialEq<RawArray<U, G, A>> for SparseArray<T>
+where
+    T: Default + PartialEq + PartialEq<U>,
+    A: RawAllocator,
+{
+    fn eq(&self, other: &RawArray<U, G, A>) -> bool {
+        self.len == other.len() && self.iter().eq(other[..].iter())
+    }
+}
+
+impl<T, U, G, A> PartialEq<SparseArray<U>> for RawArray<T, G, A>
+where
+    T: PartialEq<U>,
+    U: Default + PartialEq,
+    A: RawAllocator,
+{
+    fn eq(&self, other: &SparseArray<U>) -> bool {
+        self.len() == other.len && self[..].iter().eq(other.iter())
+    }
+}
+
+impl<T, U> PartialEq<MatrixArray<U>> for SparseArray<T>
+where
+    T: Default + PartialEq + PartialEq<U>,
+{
+    fn eq(&self, other: &MatrixArray<U>) -> bool {
+        self.len == other.size() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Default + Eq> Eq for SparseArray<T> {}
+
+impl<T: Default + PartialOrd> PartialOrd for SparseArray<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Default + Ord> Ord for SparseArray<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Default + PartialEq + Hash> Hash for SparseArray<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        crate::hash_sequence(self.iter(), state);
+    }
+}
+
+impl<T: Default + PartialEq> IntoIterator for SparseArray<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            stored: self.inner.into_iter().peekable(),
+            index: 0,
+            len: self.len,
+        }
+    }
+}
+
+impl<T: Default + PartialEq> FromIterator<T> for SparseArray<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut array = Self::new();
+        array.extend(iter);
+        array
+    }
+}
+
+// Nothing is reserved up front: how many of the new elements need storage is unknown.
+impl<T: Default + PartialEq> Extend<T> for SparseArray<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+pub struct IntoIter<T> {
+    stored: Peekable<raw_array::IntoIter<Value<T>>>,
+    index: usize,
+    len: usize,
 }
 
-#[derive(Default)]
+impl<T: Default> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index >= self.len {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        match self.stored.next_if(|v| v.index == index) {
+            Some(v) => Some(v.value),
+            None => Some(Default::default()),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: Default> ExactSizeIterator for IntoIter<T> {}
+
+#[derive(Default, Clone)]
 struct Value<T> {
     pub value: T,
     pub index: usize,
@@ -135,6 +427,10 @@ impl<T> Value<T> {
 mod tests {
     use super::*;
 
+    crate::conformance_tests!(conformance, SparseArray::new, |i| i as u64);
+    // Mostly zeros, which are not stored.
+    crate::conformance_tests!(conformance_zeros, SparseArray::new, |i| (i % 3 == 0) as u8);
+
     #[test]
     fn it_works() {
         let mut a = SparseArray::<u8>::new();
@@ -164,4 +460,117 @@ mod tests {
         println!("{}", a.repr());
         println!("{}", a);
     }
+
+    #[test]
+    fn checked_access() {
+        let mut a = SparseArray::<u8>::new();
+        a.insert(7, 3);
+        assert_eq!(Some(&7), a.try_get(3));
+        assert_eq!(Some(&0), a.try_get(1));
+        assert_eq!(None, a.try_get(4));
+        assert_eq!(0, a.set(1, 5));
+        assert_eq!(7, a.set(3, 0));
+        assert_eq!(&5, a.get(1));
+        assert_eq!(&0, a.get(3));
+        *a.get_mut(2).unwrap() += 9;
+        assert_eq!(&9, a.get(2));
+        assert!(a.get_mut(4).is_none());
+        assert_eq!(Err(IndexError::new(4, 4)), a.try_remove(4));
+        assert_eq!(Ok(5), a.try_remove(1));
+        assert_eq!(&9, a.get(1));
+        assert_eq!(3, a.size());
+    }
+
+    #[test]
+    fn get_mut_keeps_zeros_implicit() {
+        let mut a: SparseArray<u8> = [0, 0, 0, 0].into_iter().collect();
+        for index in 0..4 {
+            assert_eq!(Some(&mut 0), a.get_mut(index));
+        }
+        assert_eq!("[]", a.repr());
+        assert_eq!(1, a.inner.len());
+        a.push(0);
+        assert_eq!(0, a.inner.len());
+        *a.get_mut(1).unwrap() = 6;
+        *a.get_mut(1).unwrap() -= 6;
+        a.remove(4);
+        assert_eq!(0, a.inner.len());
+        assert_eq!(vec![0; 4], a.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "the len is 2 but the index is 2")]
+    fn remove_out_of_bounds() {
+        let mut a: SparseArray<u8> = [1, 0].into_iter().collect();
+        a.remove(2);
+    }
+
+    #[test]
+    fn iteration() {
+        let mut a = SparseArray::<u8>::new();
+        a.insert(7, 3);
+        a.insert(5, 1);
+        a.push(0);
+        a.push(9);
+        assert_eq!(
+            vec![0, 5, 0, 0, 7, 0, 9],
+            a.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(7, a.iter().len());
+        assert_eq!("[0, 5, 0, 0, 7, 0, 9]", a.to_string());
+        assert_eq!(0, SparseArray::<u8>::new().iter().count());
+    }
+
+    #[test]
+    fn owning_iteration() {
+        let mut a: SparseArray<u8> = [0, 3, 0, 0, 5].into_iter().collect();
+        a.extend([0, 7]);
+        assert_eq!(7, a.size());
+        assert_eq!("[(1, 3), (4, 5), (6, 7)]", a.repr());
+        let mut iter = a.into_iter();
+        assert_eq!(Some(0), iter.next());
+        assert_eq!(6, iter.len());
+        assert_eq!(vec![3, 0, 0, 5, 0, 7], iter.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn clone_and_compare() {
+        let a: SparseArray<u8> = [0, 3, 0, 5].into_iter().collect();
+        let b = a.clone();
+        assert_eq!(a, b);
+        let mut matrix = MatrixArray::new(3);
+        [0, 3, 0, 5].into_iter().for_each(|x| matrix.push(x));
+        assert_eq!(a, matrix);
+        assert_eq!(matrix, a);
+        let array: Array<u8> = [0, 3, 0, 5].into_iter().collect();
+        assert_eq!(a, array);
+        assert_eq!(array, a);
+        let mut longer = b.clone();
+        longer.push(0);
+        assert_ne!(a, longer);
+        assert!(a < longer);
+        assert_eq!("[0, 3, 0, 5]", format!("{:?}", a));
+    }
+
+    #[test]
+    fn bulk_removal() {
+        let mut array: SparseArray<u8> = [0, 3, 0, 0, 5, 6, 0, 7].into_iter().collect();
+        array.retain(|x| *x != 6);
+        assert_eq!("[0, 3, 0, 0, 5, 0, 7]", format!("{}", array));
+        assert_eq!(vec![0, 0, 5], array.drain(2..5).collect::<Vec<_>>());
+        assert_eq!("[0, 3, 0, 7]", format!("{}", array));
+        assert_eq!("[(1, 3), (3, 7)]", array.repr());
+        array.retain_mut(|x| {
+            *x = x.saturating_sub(3);
+            true
+        });
+        assert_eq!("[(3, 4)]", array.repr());
+        array.dedup_by_key(|x| *x);
+        assert_eq!("[0, 4]", format!("{}", array));
+        array.truncate(1);
+        assert_eq!(1, array.size());
+        assert_eq!("[]", array.repr());
+        array.clear();
+        assert_eq!(0, array.size());
+    }
 }
@@ -0,0 +1,180 @@
+use std::cell::Cell;
+
+// Allocation and copying counters, kept per `RawArray` and per thread.
+// `peak_capacity` is in elements; the thread-wide one is the largest buffer any array reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub allocations: usize,
+    pub reallocations: usize,
+    pub deallocations: usize,
+    pub bytes_moved: usize,
+    pub peak_capacity: usize,
+}
+
+thread_local! {
+    static GLOBAL: Cell<Stats> = const {
+        Cell::new(Stats {
+            allocations: 0,
+            reallocations: 0,
+            deallocations: 0,
+            bytes_moved: 0,
+            peak_capacity: 0,
+        })
+    };
+}
+
+// Everything the arrays of this thread have done since the last `reset`.
+pub fn global() -> Stats {
+    GLOBAL.with(Cell::get)
+}
+
+pub fn reset() {
+    GLOBAL.with(|global| global.set(Stats::default()));
+}
+
+// Runs `f` and returns what it cost, for containers built from several arrays.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Stats) {
+    let before = global();
+    GLOBAL.with(|global| {
+        global.set(Stats {
+            peak_capacity: 0,
+            ..before
+        })
+    });
+    let result = f();
+    let after = global();
+    GLOBAL.with(|global| {
+        global.set(Stats {
+            peak_capacity: before.peak_capacity.max(after.peak_capacity),
+            ..after
+        })
+    });
+    let stats = Stats {
+        allocations: after.allocations - before.allocations,
+        reallocations: after.reallocations - before.reallocations,
+        deallocations: after.deallocations - before.deallocations,
+        bytes_moved: after.bytes_moved - before.bytes_moved,
+        peak_capacity: after.peak_capacity,
+    };
+    (result, stats)
+}
+
+// What happened to a buffer; recorded on the array and on the thread.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Event {
+    Allocated { cap: usize },
+    Reallocated { cap: usize },
+    Deallocated,
+    Moved { bytes: usize },
+}
+
+impl Stats {
+    pub(crate) fn record(&mut self, event: Event) {
+        self.apply(event);
+        record_global(event);
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Allocated { cap } => {
+                self.allocations += 1;
+                self.peak_capacity = self.peak_capacity.max(cap);
+            }
+            Event::Reallocated { cap } => {
+                self.reallocations += 1;
+                self.peak_capacity = self.peak_capacity.max(cap);
+            }
+            Event::Deallocated => self.deallocations += 1,
+            Event::Moved { bytes } => self.bytes_moved += bytes,
+        }
+    }
+}
+
+// For buffers that outlived their array, e.g. the one owned by `IntoIter`.
+pub(crate) fn record_global(event: Event) {
+    GLOBAL.with(|global| {
+        let mut stats = global.get();
+        stats.apply(event);
+        global.set(stats);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        array::Array, matrix_array::MatrixArray, single_array::SingleArray,
+        vector_array::VectorArray, IArray,
+    };
+
+    #[test]
+    fn per_array() {
+        let mut array = Array::<u64>::new();
+        (0..1024).for_each(|x| array.push(x));
+        assert_eq!(1, array.stats().allocations);
+        assert_eq!(10, array.stats().reallocations);
+        assert_eq!(0, array.stats().bytes_moved);
+        array.insert(42, 0);
+        array.remove(1000);
+        assert_eq!(1024 * 8 + 24 * 8, array.stats().bytes_moved);
+        assert_eq!(2048, array.stats().peak_capacity);
+        array.shrink_to_fit();
+        assert_eq!(12, array.stats().reallocations);
+
+        let mut array = SingleArray::<u64>::new();
+        (0..100).for_each(|x| array.push(x));
+        assert_eq!(99, array.stats().reallocations);
+        let mut array = VectorArray::<u64>::new(10);
+        (0..100).for_each(|x| array.push(x));
+        assert_eq!(9, array.stats().reallocations);
+    }
+
+    #[test]
+    fn thread_wide() {
+        reset();
+        let (array, stats) = measure(|| {
+            let mut array = MatrixArray::<u64>::new(10);
+            (0..100).for_each(|x| array.push(x));
+            array
+        });
+        // Ten blocks plus the block table growing to 16 entries.
+        assert_eq!(10 + 1, stats.allocations);
+        assert_eq!(4, stats.reallocations);
+        assert_eq!(16, stats.peak_capacity);
+        let (_, stats) = measure(|| drop(array.into_iter()));
+        assert_eq!(11, stats.deallocations);
+        assert_eq!(global().allocations, global().deallocations);
+    }
+
+    #[test]
+    fn bulk_operations_move_the_tail_once() {
+        let mut array: Array<u64> = (0..100).collect();
+        let before = array.stats().bytes_moved;
+        array.insert_many(10, 0..50);
+        assert_eq!(90 * 8, array.stats().bytes_moved - before);
+        let before = array.stats().bytes_moved;
+        array.drain(10..60);
+        assert_eq!(90 * 8, array.stats().bytes_moved - before);
+        let before = array.stats().bytes_moved;
+        array.retain(|x| x % 2 == 0);
+        assert_eq!(49 * 8, array.stats().bytes_moved - before);
+        let mut tail = array.split_off(20);
+        assert_eq!(30 * 8, array.stats().bytes_moved - before - 49 * 8);
+        let before = array.stats().bytes_moved;
+        array.append(&mut tail);
+        assert_eq!(30 * 8, array.stats().bytes_moved - before);
+    }
+
+    #[test]
+    fn matrix_array_counts_carries_between_blocks() {
+        let mut array = MatrixArray::<u64>::new(100);
+        (0..10_000).for_each(|x| array.push(x));
+        // Ten elements shift within the first block, then each block carries its last element
+        // into the next one, the last of them into a new block.
+        let (_, stats) = measure(|| array.insert(42, 10));
+        assert_eq!((10 + 100) * 8, stats.bytes_moved);
+        let (_, stats) = measure(|| array.remove(5_070));
+        assert_eq!((29 + 50) * 8, stats.bytes_moved);
+        assert_eq!(0, stats.allocations);
+    }
+}
@@ -0,0 +1,9 @@
+// How an implementation outside the crate is checked: here the `Vec` wrapper itself.
+use array_list::{conformance, conformance_tests, Wrapper};
+
+conformance_tests!(wrapper, Wrapper::new, |i| i as i64);
+
+#[test]
+fn run_all() {
+    conformance::run_all(Wrapper::<String>::new, |i| (i % 7).to_string());
+}
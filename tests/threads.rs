@@ -0,0 +1,97 @@
+// Every container crosses threads through the public API. That `Rc` elements or an arena
+// borrowed by reference keep an array on its thread is checked by the `compile_fail`
+// doctests on `RawArray`.
+use std::{sync::Arc, thread};
+
+use array_list::{
+    allocator::{Arena, Counting, Global},
+    array::Array,
+    growth::{Factor, Fibonacci},
+    matrix_array::{self, MatrixArray},
+    priority_queue::PriorityQueue,
+    raw_array::{self, RawArray},
+    single_array::SingleArray,
+    sparse_array::{self, SparseArray},
+    vector_array::VectorArray,
+    IArray,
+};
+
+fn assert_send_sync<T: Send + Sync>() {}
+fn assert_send<T: Send>() {}
+
+#[test]
+fn send_and_sync() {
+    assert_send_sync::<Array<String>>();
+    assert_send_sync::<SingleArray<String>>();
+    assert_send_sync::<VectorArray<String>>();
+    assert_send_sync::<RawArray<String, Fibonacci, Counting>>();
+    assert_send_sync::<RawArray<String, Factor, &Counting>>();
+    assert_send_sync::<raw_array::IntoIter<String>>();
+    assert_send_sync::<MatrixArray<String>>();
+    assert_send_sync::<matrix_array::IntoIter<String>>();
+    assert_send_sync::<SparseArray<String>>();
+    assert_send_sync::<sparse_array::IntoIter<String>>();
+    assert_send_sync::<PriorityQueue<String>>();
+    // An arena can move to another thread, but its bump pointer is not shared safely.
+    assert_send::<RawArray<String, Factor, Arena>>();
+}
+
+#[test]
+fn shared_reads() {
+    let shared: Arc<Array<u64>> = Arc::new((0..1000).collect());
+    let sums: Vec<u64> = (0..4)
+        .map(|_| {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || shared.iter().sum())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(vec![499500; 4], sums);
+}
+
+#[test]
+fn containers_move_between_threads() {
+    let mut matrix = MatrixArray::new(7);
+    (0..100).for_each(|x| matrix.push(x.to_string()));
+    let mut sparse: SparseArray<u32> = [0, 1, 0, 2].into_iter().collect();
+    let mut queue = PriorityQueue::default();
+    queue.enqueue(0, String::from("low"));
+    let (matrix, sparse, queue) = thread::spawn(move || {
+        matrix.insert(String::from("front"), 0);
+        sparse.push(3);
+        queue.enqueue(1, String::from("high"));
+        (matrix, sparse, queue)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(101, matrix.size());
+    assert_eq!("front", matrix.get(0));
+    assert_eq!(vec![0, 1, 0, 2, 3], sparse.into_iter().collect::<Vec<_>>());
+    let mut items: Vec<_> = queue.into_iter().collect();
+    items.sort();
+    assert_eq!(vec!["high", "low"], items);
+
+    let mut iter = (0..10).collect::<Array<i32>>().into_iter();
+    iter.next();
+    let rest = thread::spawn(move || iter.collect::<Vec<_>>())
+        .join()
+        .unwrap();
+    assert_eq!((1..10).collect::<Vec<_>>(), rest);
+}
+
+#[test]
+fn shared_allocator() {
+    let counting = Counting::new(Global);
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let mut array = RawArray::with_growth_in(Factor::default(), &counting);
+                (0..100).for_each(|x| array.push(x));
+            });
+        }
+    });
+    assert_eq!(counting.allocations(), counting.deallocations());
+    assert_eq!(0, counting.bytes_in_use());
+}
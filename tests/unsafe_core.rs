@@ -0,0 +1,150 @@
+// Exercises the unsafe core through the public API. Meant to run under Miri as well:
+// cargo +nightly miri test --test unsafe_core
+use std::{cell::Cell, rc::Rc};
+
+use array_list::{
+    allocator::{Arena, Counting, Global, RawAllocator},
+    array::Array,
+    growth::{Exact, Factor, Fibonacci, GrowthPolicy, Linear},
+    matrix_array::MatrixArray,
+    raw_array::RawArray,
+    single_array::SingleArray,
+    vector_array::VectorArray,
+    IArray, IIterableMut,
+};
+
+// Miri is slow, so it gets smaller arrays.
+const N: usize = if cfg!(miri) { 40 } else { 1000 };
+
+#[test]
+fn rc_counts_balance() {
+    let rc = Rc::new(0);
+    {
+        let mut array: Array<Rc<i32>> = (0..N).map(|_| Rc::clone(&rc)).collect();
+        array.insert(Rc::clone(&rc), 0);
+        array.insert(Rc::clone(&rc), N / 2);
+        drop(array.remove(1));
+        drop(array.pop());
+        array.truncate(N - 5);
+        drop(array.drain(3..10));
+        let mut drain = array.drain(5..20);
+        drain.next();
+        drain.next_back();
+        drop(drain);
+        array.retain(|_| !Rc::strong_count(&rc).is_multiple_of(3));
+        array.dedup_by(|a, b| Rc::ptr_eq(a, b) && Rc::strong_count(a).is_multiple_of(2));
+        drop(array.splice(1..4, (0..7).map(|_| Rc::clone(&rc))));
+        let mut tail = array.split_off(array.len() / 2);
+        let copy = tail.clone();
+        array.append(&mut tail);
+        array.extend_from_slice(&copy);
+        array.shrink_to_fit();
+        let mut iter = array.into_iter();
+        iter.next();
+        iter.next_back();
+        assert!(Rc::strong_count(&rc) > 1);
+    }
+    assert_eq!(1, Rc::strong_count(&rc));
+
+    {
+        let mut matrix = MatrixArray::new(3);
+        (0..N).for_each(|_| matrix.push(Rc::clone(&rc)));
+        drop(matrix.remove(0));
+        matrix.insert(Rc::clone(&rc), N / 3);
+        let mut iter = matrix.into_iter();
+        iter.nth(N / 2);
+    }
+    assert_eq!(1, Rc::strong_count(&rc));
+}
+
+#[test]
+fn zero_sized_drops() {
+    thread_local! {
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Unit;
+
+    impl Drop for Unit {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+
+    let mut array: SingleArray<Unit> = (0..N).map(|_| Unit).collect();
+    array.insert(Unit, 3);
+    drop(array.drain(..10));
+    array.retain(|_| DROPS.with(Cell::get).is_multiple_of(2));
+    let remaining = array.len();
+    assert_eq!(N + 1, DROPS.with(Cell::get) + remaining);
+    drop(array);
+    assert_eq!(N + 1, DROPS.with(Cell::get));
+}
+
+#[repr(align(256))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Aligned(u16);
+
+fn check_alignment<G: GrowthPolicy, A: RawAllocator>(mut array: RawArray<Aligned, G, A>) {
+    for i in 0..N / 4 {
+        array.insert(Aligned(i as u16), i / 2);
+    }
+    for elem in array.iter() {
+        assert_eq!(0, elem as *const Aligned as usize % 256);
+    }
+    let removed = array.remove(0).0;
+    array.shrink_to_fit();
+    assert_eq!(0, array.as_ptr() as usize % 256);
+    let mut sorted: Vec<u16> = array.iter().map(|elem| elem.0).collect();
+    sorted.push(removed);
+    sorted.sort();
+    assert_eq!((0..(N / 4) as u16).collect::<Vec<_>>(), sorted);
+    let copy: Vec<Aligned> = array.iter().copied().collect();
+    array.extend_from_copy_slice(&copy);
+    assert_eq!(&copy[..], &array[..copy.len()]);
+    assert_eq!(&copy[..], &array[copy.len()..]);
+}
+
+#[test]
+fn large_alignment() {
+    check_alignment(RawArray::with_growth(Factor::default()));
+    check_alignment(RawArray::with_growth(Exact));
+    check_alignment(RawArray::with_growth(Linear::new(7)));
+    check_alignment(RawArray::with_growth(Fibonacci));
+    let arena = Arena::new(1000);
+    check_alignment(RawArray::with_growth_in(Factor::default(), &arena));
+    let counting = Counting::new(Global);
+    check_alignment(RawArray::with_growth_in(Factor::default(), &counting));
+    assert_eq!(0, counting.bytes_in_use());
+}
+
+#[test]
+fn deref_mut_aliasing() {
+    let mut array: VectorArray<i32> = (0..10).collect();
+    // Pointers from `as_mut_ptr` survive reads and writes through `Deref`/`DerefMut`.
+    let ptr = array.as_mut_ptr();
+    unsafe { *ptr = 10 };
+    assert_eq!(10, array.len());
+    array[1] = 11;
+    unsafe { *ptr.add(2) = 12 };
+    assert_eq!(&[10, 11, 12], &array[..3]);
+
+    let (left, right) = array.split_at_mut(5);
+    std::mem::swap(&mut left[0], &mut right[0]);
+    let refs: Vec<&mut i32> = array.iter_mut().collect();
+    for elem in refs {
+        *elem += 1;
+    }
+    assert_eq!(&[6, 12, 13, 4, 5, 11, 7, 8, 9, 10], &array[..]);
+
+    if let Some(elem) = array.get_mut(9) {
+        *elem = 0;
+    }
+    let first = &array[0];
+    assert_eq!(6, *first);
+    array.retain_mut(|elem| {
+        *elem *= 2;
+        *elem > 10
+    });
+    assert_eq!(&[12, 24, 26, 22, 14, 16, 18], &array[..]);
+}